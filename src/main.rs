@@ -1,31 +1,40 @@
+extern crate bincode;
 extern crate ncurses as nc;
 extern crate rand;
 extern crate regex;
+extern crate serde;
 
 #[macro_use]
 extern crate diesel;
 #[macro_use]
 extern crate lazy_static;
+#[macro_use]
+extern crate serde_derive;
 
 // TODO: move the models and schema modules into the word_list module
 mod markov;
 mod models;
 mod schema;
+mod shuffle;
+mod stats;
 mod tui;
 mod word_list;
 
-use rand::prelude::*;
+use rand::{prelude::*, StdRng};
+use shuffle::{Hider, ShuffleStrategy};
+use stats::Stats;
 use std::{
-  collections::{HashMap, HashSet},
+  collections::{HashMap, HashSet, VecDeque},
+  env,
   fs::File,
-  io::{self, prelude::*},
+  io::{self, prelude::*, BufReader},
   panic,
 };
 use tui::{
   controls::*,
   element::{self as el, Element},
 };
-use word_list::WordList;
+use word_list::{KeyFilters, WordList, WordPattern};
 
 fn dump_line(win: nc::WINDOW, y: i32, line: &str) {
   nc::wmove(win, y, 0);
@@ -34,19 +43,321 @@ fn dump_line(win: nc::WINDOW, y: i32, line: &str) {
   nc::wrefresh(win);
 }
 
+// Sorting strategies stay applied as the letters change; anything else just
+// rearranges them once
+fn apply_strategy<'a>(
+  word_box: &mut WordBox,
+  strategies: &[Box<ShuffleStrategy + 'a>],
+  index: usize,
+  rng: &mut StdRng,
+) {
+  let strategy = &strategies[index];
+
+  match strategy.sorter() {
+    Some(sorter) => word_box.set_sorter(index, sorter),
+    None => word_box.shuffle(index, |s| strategy.shuffle(s, rng)),
+  }
+}
+
+// Loads a letter model saved by tools/letter-model, dropping any contexts
+// longer than the game is using
+fn load_model(
+  path: &str,
+  order: usize,
+) -> Result<markov::FreqTable<char>, String> {
+  let file = File::open(path).map_err(|e| e.to_string())?;
+  let mut model: markov::FreqTable<char> =
+    markov::load(BufReader::new(file)).map_err(|e| e.to_string())?;
+
+  model.retain(|k, _| k.len() <= order);
+
+  Ok(model)
+}
+
+// Dumps a round's letter model, for checking up on how shuffles are made
+fn write_freq_log(
+  round_seed: u64,
+  order: usize,
+  table: &markov::FreqTable<char>,
+) -> File {
+  let mut file = File::create("freq.log").unwrap();
+
+  writeln!(file, "seed: {}", round_seed).unwrap();
+  writeln!(file, "order: {}", order).unwrap();
+  writeln!(file, "table:").unwrap();
+
+  for (from, tos) in table {
+    let from: String = from.iter().collect();
+
+    for (to, freq) in tos {
+      writeln!(file, "  {} -> {}: {}", from, to, freq).unwrap();
+    }
+  }
+
+  file
+}
+
+fn write_samples(file: &mut File, markov: &markov::Markov<char>) {
+  writeln!(file, "samples:").unwrap();
+
+  // Don't touch the round's RNG, so the samples don't affect the shuffles
+  let mut sample_rng = thread_rng();
+  let samples: Vec<char> = markov
+    .rand_seed(&mut sample_rng)
+    .take(20)
+    .cloned()
+    .collect();
+
+  for s in &samples {
+    let line: String = markov.iter(s, &mut sample_rng).take(40).collect();
+
+    writeln!(file, "{}", line).unwrap();
+  }
+}
+
+fn next_revealed(
+  panel: &[(&String, &el::ElemWrapper<MatchBox>)],
+  from: Option<usize>,
+  forward: bool,
+) -> Option<usize> {
+  let n = panel.len();
+
+  (1..n + 1)
+    .map(|d| match (from, forward) {
+      (Some(i), true) => (i + d) % n,
+      (Some(i), false) => (i + n - d) % n,
+      (None, true) => d - 1,
+      (None, false) => n - d,
+    })
+    .find(|i| panel[*i].1.borrow().revealed())
+}
+
+fn select_match_box(
+  words: &WordList,
+  panel: &[(&String, &el::ElemWrapper<MatchBox>)],
+  definition_box: &el::ElemWrapper<DefinitionBox>,
+  selected: &mut Option<usize>,
+  to: usize,
+  highlight: bool,
+) {
+  if let Some(i) = *selected {
+    panel[i].1.borrow_mut().set_style(MatchBoxStyle::Normal);
+  }
+
+  let (norm, match_box) = panel[to];
+  let mut match_box = match_box.borrow_mut();
+
+  if highlight {
+    match_box.set_style(MatchBoxStyle::Select);
+  }
+
+  definition_box.borrow_mut().set_word(
+    Some(match_box.form().full.clone()),
+    words.get_definitions(norm),
+  );
+
+  *selected = Some(to);
+}
+
+// Returns false if the user asked to quit
+fn browse(win: nc::WINDOW, words: &WordList) -> bool {
+  let modes = ["prefix", "suffix", "blanks", "contains"];
+  let mut mode = 0;
+  let mut query = String::new();
+
+  let browse_box = el::wrap(BrowseBox::new());
+
+  browse_box.borrow_mut().set_query(modes[mode], &query);
+
+  let ui_root = UiRoot::new(win, el::add_ref(&browse_box));
+
+  ui_root.resize();
+
+  loop {
+    match nc::wgetch(win) {
+      0x03 | 0x04 => return false,
+      0x1B => return true,                     // ESC
+      0x09 => mode = (mode + 1) % modes.len(), // HT
+      0x7F | nc::KEY_BACKSPACE => {
+        query.pop();
+      }
+      nc::KEY_BTAB => mode = (mode + modes.len() - 1) % modes.len(),
+      nc::KEY_NPAGE => {
+        browse_box.borrow_mut().scroll_by(1);
+        continue;
+      }
+      nc::KEY_PPAGE => {
+        browse_box.borrow_mut().scroll_by(-1);
+        continue;
+      }
+      nc::KEY_RESIZE => {
+        ui_root.resize();
+        continue;
+      }
+      ch => {
+        if ch < nc::KEY_MIN {
+          let ch = ch as u8 as char;
+
+          if ch.is_control() {
+            continue;
+          }
+
+          query.extend(ch.to_lowercase());
+        } else {
+          continue;
+        }
+      }
+    }
+
+    let results = if query.is_empty() {
+      Vec::new()
+    } else {
+      let pattern = match mode {
+        0 => WordPattern::Prefix(query.clone()),
+        1 => WordPattern::Suffix(query.clone()),
+        2 => WordPattern::Blanks(query.clone()),
+        3 => WordPattern::Contains(query.clone()),
+        _ => unreachable!(),
+      };
+
+      words.find_words(&pattern)
+    };
+
+    let mut browse_box = browse_box.borrow_mut();
+
+    browse_box.set_query(modes[mode], &query);
+    browse_box.set_results(results);
+  }
+}
+
 fn main() {
   panic::catch_unwind(|| {
     nc::endwin();
   }).unwrap();
 
+  let mut args: VecDeque<_> = env::args().collect();
+  args.pop_front(); // drop argv[0]
+
+  let mut avoid_recent: usize = 0;
+  let mut seed: Option<u64> = None;
+  let mut order: usize = 1;
+  let mut strategy: usize = 0;
+  let strategy_names = shuffle::names();
+  let mut debug_log = false;
+  let mut input_mode = InputMode::Free;
+  let mut model_file: Option<String> = None;
+
+  while let Some(arg) = args.pop_front() {
+    match arg.as_str() {
+      "--avoid-recent" => {
+        avoid_recent = match args.pop_front().map(|a| a.parse()) {
+          Some(Ok(n)) if n <= word_list::MAX_AVOID => n,
+          _ => {
+            writeln!(
+              io::stderr(),
+              "--avoid-recent expects a count of at most {}",
+              word_list::MAX_AVOID
+            ).unwrap();
+            return;
+          }
+        }
+      }
+      "--seed" => {
+        seed = match args.pop_front().map(|a| a.parse()) {
+          Some(Ok(n)) => Some(n),
+          _ => {
+            writeln!(io::stderr(), "--seed expects a number").unwrap();
+            return;
+          }
+        }
+      }
+      "--order" => {
+        order = match args.pop_front().map(|a| a.parse()) {
+          Some(Ok(n)) if n > 0 => n,
+          _ => {
+            writeln!(io::stderr(), "--order expects a positive number")
+              .unwrap();
+            return;
+          }
+        }
+      }
+      "--shuffle" => {
+        strategy = match args
+          .pop_front()
+          .and_then(|a| strategy_names.iter().position(|n| *n == a))
+        {
+          Some(i) => i,
+          None => {
+            writeln!(
+              io::stderr(),
+              "--shuffle expects one of: {}",
+              strategy_names.join(", ")
+            ).unwrap();
+            return;
+          }
+        }
+      }
+      "--debug-log" => debug_log = true,
+      "--input" => {
+        input_mode = match args.pop_front().and_then(|a| InputMode::parse(&a))
+        {
+          Some(m) => m,
+          None => {
+            writeln!(io::stderr(), "--input expects free, strict, or tiles")
+              .unwrap();
+            return;
+          }
+        }
+      }
+      "--model" => {
+        model_file = match args.pop_front() {
+          Some(f) => Some(f),
+          None => {
+            writeln!(io::stderr(), "--model expects a filename").unwrap();
+            return;
+          }
+        }
+      }
+      a => {
+        writeln!(io::stderr(), "unexpected argument '{}'", a).unwrap();
+        return;
+      }
+    }
+  }
+
   let words = WordList::new("etc/words.sqlite3");
+  let stats = Stats::new("etc/stats.sqlite3");
+
+  let global_model = match model_file {
+    Some(f) => match load_model(&f, order) {
+      Ok(m) => m,
+      Err(e) => {
+        writeln!(io::stderr(), "couldn't load model from {}: {}", f, e)
+          .unwrap();
+        return;
+      }
+    },
+    None => words.get_transitions(order),
+  };
+
+  let dict: HashSet<String> =
+    words.get_words(shuffle::HIDE_MIN_LEN).into_iter().collect();
+
+  // Each round gets its own seed, so any round can be replayed from its seed
+  let mut seed_rng = match seed {
+    Some(s) => shuffle::seeded_rng(s),
+    None => StdRng::from_rng(thread_rng()).unwrap(),
+  };
 
   let mut len: Option<usize> = None;
 
   'main: loop {
+    let round_seed: u64 = seed_rng.gen();
+    let mut rng = shuffle::seeded_rng(round_seed);
+
     let key;
     let set = {
-      let mut keys = loop {
+      key = loop {
         if let None = len {
           let mut len_str = String::new();
 
@@ -69,86 +380,62 @@ fn main() {
 
         let _len = len.unwrap();
 
-        let keys = words.get_set_keys(&_len);
+        let filters = KeyFilters {
+          avoid: if avoid_recent > 0 {
+            stats.recent_keys(_len, avoid_recent)
+          } else {
+            Vec::new()
+          },
+        };
 
-        match keys.len() {
-          0 => {
+        // If every key was played recently, just pick any of them
+        match words
+          .random_set_key(_len, &filters, &mut rng)
+          .or_else(|| words.random_set_key(_len, &Default::default(), &mut rng))
+        {
+          Some(k) => break k,
+          None => {
             writeln!(io::stderr(), "no words found of length {}", _len)
               .unwrap();
             len = None;
             continue;
           }
-          _ => break keys,
         }
       };
 
-      let nkeys = keys.len();
-
-      key = keys.remove(rand::thread_rng().gen_range(0, nkeys));
-
       words.get_set(&key)
     };
 
-    let markov = {
-      use std::collections::hash_map::Entry::*;
-
-      let mut table = markov::analyze_corpus(
-        set.iter().map(|s| ((s.len() as f64).powf(3.4), s.chars())),
-      );
-      let chars: HashSet<_> = set.iter().flat_map(|s| s.chars()).collect();
-
-      let pad = table
-        .values()
-        .flat_map(|t| t.values())
-        .fold(0.0, |s, c| s + c) / 100.0;
+    let bonus: HashSet<String> = words.get_bonus(&key).into_iter().collect();
+    let mut found_bonus: HashSet<String> = HashSet::new();
 
-      for chr in &chars {
-        let tos = match table.entry(*chr) {
-          Vacant(v) => v.insert(HashMap::new()),
-          Occupied(o) => o.into_mut(),
-        };
-
-        for chr in &chars {
-          match tos.entry(*chr) {
-            Vacant(v) => {
-              v.insert(pad);
-            }
-            Occupied(o) => {
-              let o = o.into_mut();
-              *o = *o + pad;
-            }
-          }
-        }
-      }
-
-      let mut file = File::create("freq.log").unwrap();
-
-      writeln!(file, "table:").unwrap();
+    let markov = {
+      let table = shuffle::round_model(&set, &global_model, order);
 
-      for (from, tos) in &table {
-        for (to, freq) in tos {
-          writeln!(file, "  {} -> {}: {}", from, to, freq).unwrap();
-        }
-      }
+      let freq_log = if debug_log {
+        Some(write_freq_log(round_seed, order, &table))
+      } else {
+        None
+      };
 
       let markov = markov::Markov::new(table);
 
-      writeln!(file, "samples:").unwrap();
-
-      for s in markov.rand_seed().take(20) {
-        let line: String = markov.iter(s).take(40).collect();
-
-        writeln!(file, "{}", line).unwrap();
+      if let Some(mut file) = freq_log {
+        write_samples(&mut file, &markov);
       }
 
       markov
     };
 
+    let strategies =
+      shuffle::strategies(&markov, Hider::new(&set, &dict, &markov));
+
     let mut remain: HashSet<&String> = set.iter().collect();
 
     let win = nc::initscr();
     nc::start_color();
-    nc::cbreak();
+    // Raw mode, so Ctrl+Z and Ctrl+Y reach the game instead of suspending it
+    nc::raw();
     nc::noecho();
     nc::keypad(win, true);
 
@@ -168,8 +455,16 @@ fn main() {
     let hl_pair: i32 = 5;
     nc::init_pair(hl_pair as i16, 2, 0);
 
+    let bonus_pair: i32 = 6;
+    nc::init_pair(bonus_pair as i16, 6, 0);
+
+    let select_pair: i32 = 7;
+    nc::init_pair(select_pair as i16, 0, 7);
+
     let word_box = el::wrap(WordBox::new(
       key.clone(),
+      input_mode,
+      strategy,
       ghost_pair,
       bad_ghost_pair,
       auto_ghost_pair,
@@ -184,26 +479,39 @@ fn main() {
         norm,
         forms
           .into_iter()
-          .map(|form| el::wrap(MatchBox::new(form, reveal_pair, hl_pair)))
+          .map(|form| {
+            el::wrap(MatchBox::new(form, reveal_pair, hl_pair, select_pair))
+          })
           .collect(),
       );
     }
 
+    let panel: Vec<_> = set
+      .iter()
+      .flat_map(|i| match_boxes[i].iter().map(move |b| (i, b)))
+      .collect();
+
     let match_box_panel = el::wrap(WrapBox::new(
-      set
-        .iter()
-        .flat_map(|i| &match_boxes[i])
-        .map(|b| el::add_ref(b)),
+      panel.iter().map(|&(_, b)| el::add_ref(b)),
       WrapMode::Cols,
       WrapAlign::Begin,
       3,
     ));
 
+    let bonus_box = el::wrap(BonusBox::new(bonus_pair));
+
+    let definition_box = el::wrap(DefinitionBox::new());
+    let status_box = el::wrap(StatusBox::new());
+    let mut selected: Option<usize> = None;
+
     let mut hl_match_boxes: Option<&Vec<el::ElemWrapper<MatchBox>>> = None;
 
     let center_test = el::wrap(TestView::new(
       el::add_ref(&word_box),
       el::add_ref(&match_box_panel),
+      el::add_ref(&bonus_box),
+      el::add_ref(&definition_box),
+      el::add_ref(&status_box),
     ));
 
     let ui_root = UiRoot::new(win, el::add_ref(&center_test));
@@ -211,12 +519,40 @@ fn main() {
     ui_root.resize();
 
     while remain.len() > 0 {
+      {
+        // Undo and redo can change the strategy too, so this is kept in step
+        // after every key
+        let mut word_box = word_box.borrow_mut();
+        let name = strategy_names[word_box.strategy()];
+
+        status_box
+          .borrow_mut()
+          .set_text(format!("shuffle: {}", name));
+        word_box.render_cur();
+      }
+
       // TODO: handle modifier keys better
       // TODO: up and down should be history controls, not text editing controls
       match nc::wgetch(win) {
-        0x04 => break 'main,
-        0x09 => word_box.borrow_mut().shuffle(&markov), // HT
-        0x17 => word_box.borrow_mut().clear(),          // ETB (ctrl+bksp)
+        0x03 | 0x04 => break 'main,
+        0x06 => {
+          // ACK (ctrl+f)
+          if !browse(win, &words) {
+            break 'main;
+          }
+
+          ui_root.resize();
+        }
+        0x09 => {
+          // HT
+          let mut word_box = word_box.borrow_mut();
+          let current = word_box.strategy();
+
+          apply_strategy(&mut word_box, &strategies, current, &mut rng)
+        }
+        0x17 => word_box.borrow_mut().clear(), // ETB (ctrl+bksp)
+        0x19 => word_box.borrow_mut().redo(),  // EM (ctrl+y)
+        0x1A => word_box.borrow_mut().undo(),  // SUB (ctrl+z)
         0x1B => {
           // ESC
 
@@ -241,8 +577,10 @@ fn main() {
 
           word_box.borrow_mut().render_cur();
 
+          stats.record(&key, false);
+
           match nc::wgetch(win) {
-            0x04 => break 'main,
+            0x03 | 0x04 => break 'main,
             _ => {}
           }
 
@@ -259,6 +597,8 @@ fn main() {
             }
           }
 
+          let mut relayout = false;
+
           {
             let mut word_box = word_box.borrow_mut();
 
@@ -285,36 +625,105 @@ fn main() {
 
                 success
               }
-              None => {
+              None => if bonus.contains(word_box.buf()) {
+                word_box.set_bad(false);
+
+                if found_bonus.insert(word_box.buf().clone()) {
+                  let form = words
+                    .get_form(word_box.buf())
+                    .into_iter()
+                    .next()
+                    .map_or_else(|| word_box.buf().clone(), |f| f.full);
+
+                  bonus_box
+                    .borrow_mut()
+                    .add(form, word_box.buf().chars().count());
+                  relayout = true;
+
+                  true
+                } else {
+                  false
+                }
+              } else {
                 let bad = word_box.buf().len() > 0;
                 word_box.set_bad(bad);
                 false
-              }
+              },
             };
 
             if success {
-              word_box.set_auto_sort(false);
-              word_box.clear();
+              if let Some(i) =
+                panel.iter().position(|&(n, _)| n == word_box.buf())
+              {
+                select_match_box(
+                  &words,
+                  &panel,
+                  &definition_box,
+                  &mut selected,
+                  i,
+                  false,
+                );
+              }
+
+              word_box.reset();
             } else if word_box.auto_sort() {
               word_box.render_cur();
             } else {
               word_box.clear();
             }
           }
+
+          if relayout {
+            ui_root.resize();
+          }
         }
         0x7F => word_box.borrow_mut().del_left(), // DEL (bksp)
         nc::KEY_DOWN => word_box.borrow_mut().end(),
         nc::KEY_UP => word_box.borrow_mut().home(),
+        nc::KEY_LEFT if input_mode == InputMode::Tiles => {
+          word_box.borrow_mut().select_tile_by(-1)
+        }
+        nc::KEY_RIGHT if input_mode == InputMode::Tiles => {
+          word_box.borrow_mut().select_tile_by(1)
+        }
         nc::KEY_LEFT => word_box.borrow_mut().left(),
         nc::KEY_RIGHT => word_box.borrow_mut().right(),
         nc::KEY_HOME => word_box.borrow_mut().home(),
         nc::KEY_BACKSPACE => word_box.borrow_mut().clear(), // (shift+bksp/ctrl+bksp)
         nc::KEY_DC => word_box.borrow_mut().del_right(),
+        nc::KEY_NPAGE => {
+          if let Some(i) = next_revealed(&panel, selected, true) {
+            select_match_box(
+              &words,
+              &panel,
+              &definition_box,
+              &mut selected,
+              i,
+              true,
+            );
+            word_box.borrow_mut().render_cur();
+          }
+        }
+        nc::KEY_PPAGE => {
+          if let Some(i) = next_revealed(&panel, selected, false) {
+            select_match_box(
+              &words,
+              &panel,
+              &definition_box,
+              &mut selected,
+              i,
+              true,
+            );
+            word_box.borrow_mut().render_cur();
+          }
+        }
         nc::KEY_BTAB => {
+          // (shift+tab)
           let mut word_box = word_box.borrow_mut();
-          let val = !word_box.auto_sort();
-          word_box.set_auto_sort(val);
-        } // (shift+tab)
+          let next = (word_box.strategy() + 1) % strategies.len();
+
+          apply_strategy(&mut word_box, &strategies, next, &mut rng)
+        }
         nc::KEY_END => word_box.borrow_mut().end(),
         nc::KEY_RESIZE => ui_root.resize(),
         0o1051 => word_box.borrow_mut().home(), // ctrl+left somehow?
@@ -325,7 +734,13 @@ fn main() {
           if ch < nc::KEY_MIN {
             let ch = ch as u8 as char;
 
-            if !ch.is_control() {
+            if word_box.mode() == InputMode::Tiles && ch == ' ' {
+              word_box.pick_selected();
+            } else if word_box.mode() == InputMode::Tiles && ch.is_digit(10) {
+              // Tiles are numbered 1 through 9, then 0 for the tenth
+              let n = ch.to_digit(10).unwrap() as usize;
+              word_box.pick((n + 9) % 10);
+            } else if !ch.is_control() {
               let s = ch.to_lowercase().to_string();
               word_box.put(&s);
             } else {
@@ -340,6 +755,13 @@ fn main() {
       }
     }
 
+    // Keep whichever strategy was last used for the next round
+    strategy = word_box.borrow().strategy();
+
+    if remain.is_empty() {
+      stats.record(&key, true);
+    }
+
     nc::endwin();
   }
 
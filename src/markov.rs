@@ -1,13 +1,19 @@
-use rand::{self, prelude::*};
+use bincode;
+use rand::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
 use std::{
-  cmp::Ordering,
-  collections::{BTreeMap, Bound, HashMap},
+  cmp::{self, Ordering},
+  collections::{BTreeMap, Bound, HashMap, HashSet},
   hash::Hash,
+  io::{Read, Write},
 };
 
-type FreqTable<T> = HashMap<T, HashMap<T, f64>>;
+// Maps a context (the last few items, oldest first) to how often each item
+// followed it.  Contexts of every length up to the order are kept so lookups
+// can back off to shorter ones.
+pub type FreqTable<T> = HashMap<Vec<T>, HashMap<T, f64>>;
 
-#[derive(Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
 struct TotalDouble(f64);
 
 impl Eq for TotalDouble {}
@@ -18,23 +24,32 @@ impl Ord for TotalDouble {
   }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Markov<T>
 where
   T: Eq,
   T: Hash,
 {
-  table: HashMap<T, BTreeMap<TotalDouble, T>>,
+  order: usize,
+  table: HashMap<Vec<T>, BTreeMap<TotalDouble, T>>,
 }
 
 impl<T> Markov<T>
 where
   T: Eq,
   T: Hash,
+  T: Ord,
 {
   pub fn new(freq: FreqTable<T>) -> Self {
+    let order = freq.keys().fold(1, |o, k| cmp::max(o, k.len()));
     let mut table = HashMap::new();
 
     for (from, tos) in freq {
+      // Lay transitions out in a fixed order so a seeded Rng always picks the
+      // same ones, regardless of how the HashMap happened to iterate
+      let mut tos: Vec<_> = tos.into_iter().collect();
+      tos.sort_by(|(a, _), (b, _)| a.cmp(b));
+
       let mut fold = 0.0;
 
       let mut new_tos = BTreeMap::new();
@@ -53,83 +68,172 @@ where
       );
     }
 
-    Self { table }
+    Self { order, table }
+  }
+
+  pub fn order(&self) -> usize {
+    self.order
+  }
+
+  // Returns the chance of each item following the longest context at the end
+  // of `history` that was ever seen, along with how long that context was
+  pub fn probabilities(&self, history: &[T]) -> (usize, Vec<(&T, f64)>) {
+    let n = cmp::min(self.order, history.len());
+
+    (1..n + 1)
+      .rev()
+      .filter_map(|n| {
+        self
+          .table
+          .get(&history[history.len() - n..])
+          .map(|map| (n, probabilities(map)))
+      })
+      .next()
+      .unwrap_or((0, Vec::new()))
   }
 
-  pub fn iter<'a>(&'a self, seed: &'a T) -> MarkovIter<'a, T>
+  // Returns the transitions for each context at the end of `history` that was
+  // ever seen, longest first, so callers can back off to shorter ones
+  fn lookup(&self, history: &[T]) -> Vec<&BTreeMap<TotalDouble, T>> {
+    let n = cmp::min(self.order, history.len());
+
+    (1..n + 1)
+      .rev()
+      .filter_map(|n| self.table.get(&history[history.len() - n..]))
+      .collect()
+  }
+
+  pub fn iter<'a, R>(&'a self, seed: &T, rng: &'a mut R) -> MarkovIter<'a, T, R>
   where
     T: 'a,
+    T: Clone,
+    R: Rng,
   {
     MarkovIter {
       chain: self,
-      state: Some(seed),
+      state: Some(seed.clone()),
+      history: Vec::new(),
+      rng,
     }
   }
 
-  pub fn iter_counted<'a>(
+  // Generates a sequence starting with `seed` and using up exactly the items
+  // in `remain`, which must include the seed.  Each step only considers items
+  // with some count left, so this always ends after as many steps as there are
+  // items.  If the seed isn't there (e.g. `remain` is empty), nothing is
+  // generated.
+  pub fn iter_counted<'a, R>(
     &'a self,
-    seed: &'a T,
+    seed: &T,
     mut remain: HashMap<T, usize>,
-  ) -> MarkovIterCounted<'a, T>
+    rng: &'a mut R,
+  ) -> MarkovIterCounted<'a, T, R>
   where
     T: 'a,
+    T: Clone,
+    R: Rng,
   {
-    match remain.get_mut(seed) {
-      Some(n) => if *n > 0 {
+    let state = match remain.get_mut(seed) {
+      Some(n) if *n > 0 => {
         *n = *n - 1;
-      } else {
-        panic!()
-      },
-      None => panic!(),
-    }
+        Some(seed.clone())
+      }
+      _ => None,
+    };
 
     let nremain = remain.values().fold(0, |s, n| s + n);
 
     MarkovIterCounted {
       chain: self,
-      state: Some(seed),
+      state,
+      history: Vec::new(),
       remain,
       nremain,
+      rng,
     }
   }
 
-  pub fn rand_seed<'a>(&'a self) -> MarkovRandSeed<'a, T> {
-    MarkovRandSeed {
-      keys: self.table.keys().collect(),
-    }
+  pub fn rand_seed<'a, R>(&'a self, rng: &'a mut R) -> MarkovRandSeed<'a, T, R>
+  where
+    R: Rng,
+  {
+    let mut keys: Vec<_> = self
+      .table
+      .keys()
+      .filter(|k| k.len() == 1)
+      .map(|k| &k[0])
+      .collect();
+    keys.sort();
+
+    MarkovRandSeed { keys, rng }
   }
 }
 
-pub struct MarkovIter<'a, T>
+// Turns a cumulative transition map back into the chance of each item
+fn probabilities<T>(map: &BTreeMap<TotalDouble, T>) -> Vec<(&T, f64)> {
+  let mut iter = map.iter().peekable();
+  let mut probs = Vec::new();
+
+  while let Some((TotalDouble(lo), val)) = iter.next() {
+    let hi = iter.peek().map_or(1.0, |(TotalDouble(k), _)| *k);
+
+    probs.push((val, hi - lo));
+  }
+
+  probs
+}
+
+pub struct MarkovIter<'a, T, R>
 where
   T: Eq,
   T: Hash,
   T: 'a,
+  R: Rng + 'a,
 {
   chain: &'a Markov<T>,
-  state: Option<&'a T>,
-  // TODO: keep an Rng handy
+  state: Option<T>,
+  history: Vec<T>,
+  rng: &'a mut R,
 }
 
-impl<'a, T> Iterator for MarkovIter<'a, T>
+// Appends an item to a context, dropping the oldest items once it's longer
+// than the chain's order
+fn push_history<T>(history: &mut Vec<T>, item: T, order: usize) {
+  history.push(item);
+
+  if history.len() > order {
+    let excess = history.len() - order;
+    history.drain(..excess);
+  }
+}
+
+impl<'a, T, R> Iterator for MarkovIter<'a, T, R>
 where
   T: Eq,
   T: Hash,
+  T: Ord,
+  T: Clone,
+  R: Rng,
 {
-  type Item = &'a T;
+  type Item = T;
 
   fn next(&mut self) -> Option<Self::Item> {
-    let state = self.state;
+    let state = self.state.take();
+
+    if let Some(ref state) = state {
+      push_history(&mut self.history, state.clone(), self.chain.order);
+
+      let rng = &mut self.rng;
 
-    if let Some(state) = state {
-      self.state = self.chain.table.get(state).map(|map| {
-        let f = TotalDouble(rand::thread_rng().gen_range(0.0, 1.0));
+      self.state = self.chain.lookup(&self.history).first().map(|map| {
+        let f = TotalDouble(rng.gen_range(0.0, 1.0));
 
         map
           .range((Bound::Unbounded, Bound::Included(f)))
           .next_back()
           .unwrap()
           .1
+          .clone()
       });
     }
 
@@ -137,84 +241,144 @@ where
   }
 }
 
-pub struct MarkovIterCounted<'a, T>
+pub struct MarkovIterCounted<'a, T, R>
 where
   T: Eq,
   T: Hash,
   T: 'a,
+  R: Rng + 'a,
 {
   chain: &'a Markov<T>,
-  state: Option<&'a T>,
+  state: Option<T>,
+  history: Vec<T>,
   remain: HashMap<T, usize>,
   nremain: usize,
-  // TODO: keep an Rng handy
+  rng: &'a mut R,
 }
 
-impl<'a, T> Iterator for MarkovIterCounted<'a, T>
+impl<'a, T, R> MarkovIterCounted<'a, T, R>
 where
   T: Eq,
   T: Hash,
+  T: Ord,
+  T: Clone,
+  R: Rng,
+{
+  // Picks the next item from what's left, weighting each by how likely the
+  // chain is to move to it and how many of it remain.  Contexts that can't
+  // reach anything that's left are backed off from, and if none can, this
+  // falls back to weighting by count alone.  Returns None once nothing is
+  // left.
+  fn pick(&mut self) -> Option<T> {
+    if self.nremain == 0 {
+      return None;
+    }
+
+    let mut weights: Vec<(&T, f64)> = Vec::new();
+
+    for map in self.chain.lookup(&self.history) {
+      for (val, p) in probabilities(map) {
+        if let Some(&n) = self.remain.get(val) {
+          if n > 0 && p > 0.0 {
+            weights.push((val, p * n as f64));
+          }
+        }
+      }
+
+      if !weights.is_empty() {
+        break;
+      }
+    }
+
+    if weights.is_empty() {
+      weights = self
+        .remain
+        .iter()
+        .filter(|(_, &n)| n > 0)
+        .map(|(k, &n)| (k, n as f64))
+        .collect();
+
+      // Sorted so a seeded Rng gives the same result every time
+      weights.sort_by(|(a, _), (b, _)| a.cmp(b));
+    }
+
+    let total = weights.iter().fold(0.0, |s, (_, w)| s + w);
+    let mut f = self.rng.gen_range(0.0, total);
+    let mut picked = None;
+
+    for (val, weight) in weights {
+      picked = Some(val);
+
+      if f < weight {
+        break;
+      }
+
+      f = f - weight;
+    }
+
+    // Rounding can carry f past the last weight, in which case that's the pick
+    let picked = picked.unwrap().clone();
+
+    let n = self.remain.get_mut(&picked).unwrap();
+    *n = *n - 1;
+    self.nremain = self.nremain - 1;
+
+    Some(picked)
+  }
+}
+
+impl<'a, T, R> Iterator for MarkovIterCounted<'a, T, R>
+where
+  T: Eq,
+  T: Hash,
+  T: Ord,
+  T: Clone,
   T: 'a,
+  R: Rng,
 {
-  type Item = &'a T;
+  type Item = T;
 
   fn next(&mut self) -> Option<Self::Item> {
-    let state = self.state;
-
-    if let Some(state) = state {
-      // TODO: this approach is really dumb and susceptible to hanging; it should
-      //       probably be fixed by constructing a temporary state table
-      self.state = if self.nremain > 0 {
-        self.chain.table.get(state).map(|map| loop {
-          let f = TotalDouble(rand::thread_rng().gen_range(0.0, 1.0));
-
-          let (_, val) = map
-            .range((Bound::Unbounded, Bound::Included(f)))
-            .next_back()
-            .unwrap();
-
-          if let Some(n) = self.remain.get_mut(val) {
-            if *n > 0 {
-              *n = *n - 1;
-              self.nremain = self.nremain - 1;
-              break val;
-            }
-          }
-        })
-      } else {
-        None
-      };
+    let state = self.state.take();
+
+    if let Some(ref state) = state {
+      push_history(&mut self.history, state.clone(), self.chain.order);
+
+      self.state = self.pick();
     }
 
     state
   }
 }
 
-pub struct MarkovRandSeed<'a, T>
+pub struct MarkovRandSeed<'a, T, R>
 where
   T: Eq,
   T: Hash,
   T: 'a,
+  R: Rng + 'a,
 {
   keys: Vec<&'a T>,
-  // TODO: keep an Rng handy
+  rng: &'a mut R,
 }
 
-impl<'a, T> Iterator for MarkovRandSeed<'a, T>
+impl<'a, T, R> Iterator for MarkovRandSeed<'a, T, R>
 where
   T: Eq,
   T: Hash,
   T: 'a,
+  R: Rng,
 {
   type Item = &'a T;
 
   fn next(&mut self) -> Option<Self::Item> {
-    let i = rand::thread_rng().gen_range(0, self.keys.len());
+    let i = self.rng.gen_range(0, self.keys.len());
     Some(self.keys[i])
   }
 }
 
-pub fn analyze_corpus<I, J, T>(i: I) -> FreqTable<T>
+// Counts transitions from every context of up to `order` items in the corpus
+pub fn analyze_corpus<I, J, T>(i: I, order: usize) -> FreqTable<T>
 where
   I: IntoIterator<Item = (f64, J)>,
   J: IntoIterator<Item = T>,
@@ -225,16 +389,16 @@ where
   let mut table = FreqTable::new();
 
   for (weight, j) in i {
-    let mut prev = None;
+    let items: Vec<T> = j.into_iter().collect();
 
-    for el in j {
-      use std::collections::hash_map::Entry::*;
+    for k in 1..items.len() {
+      for n in 1..cmp::min(order, k) + 1 {
+        use std::collections::hash_map::Entry::*;
 
-      if let Some(prev) = prev {
-        match match table.entry(prev) {
+        match match table.entry(items[k - n..k].to_vec()) {
           Vacant(v) => v.insert(HashMap::new()),
           Occupied(o) => o.into_mut(),
-        }.entry(el.clone())
+        }.entry(items[k].clone())
         {
           Vacant(v) => {
             v.insert(weight);
@@ -245,10 +409,225 @@ where
           }
         }
       }
-
-      prev = Some(el);
     }
   }
 
   table
 }
+
+// Mixes several tables into one, keeping only contexts and transitions made
+// of the given items.  Each context's transitions are normalized before being
+// weighted, so a big table doesn't drown out a small one.  `smoothing` is then
+// spread over every single-item transition, so that nothing is impossible.
+pub fn blend<T>(
+  tables: &[(f64, &FreqTable<T>)],
+  items: &HashSet<T>,
+  smoothing: f64,
+) -> FreqTable<T>
+where
+  T: Eq,
+  T: Hash,
+  T: Clone,
+{
+  use std::collections::hash_map::Entry::*;
+
+  let mut blended = FreqTable::new();
+
+  for &(weight, table) in tables {
+    for (from, tos) in table {
+      if !from.iter().all(|i| items.contains(i)) {
+        continue;
+      }
+
+      let tos: Vec<_> =
+        tos.iter().filter(|(to, _)| items.contains(to)).collect();
+      let total = tos.iter().fold(0.0, |s, (_, f)| s + *f);
+
+      if total <= 0.0 {
+        continue;
+      }
+
+      let new_tos = match blended.entry(from.clone()) {
+        Vacant(v) => v.insert(HashMap::new()),
+        Occupied(o) => o.into_mut(),
+      };
+
+      for (to, freq) in tos {
+        let f = weight * freq / total;
+
+        match new_tos.entry(to.clone()) {
+          Vacant(v) => {
+            v.insert(f);
+          }
+          Occupied(o) => {
+            let o = o.into_mut();
+            *o = *o + f;
+          }
+        }
+      }
+    }
+  }
+
+  let pad = smoothing / items.len() as f64;
+
+  for from in items {
+    let tos = match blended.entry(vec![from.clone()]) {
+      Vacant(v) => v.insert(HashMap::new()),
+      Occupied(o) => o.into_mut(),
+    };
+
+    for to in items {
+      match tos.entry(to.clone()) {
+        Vacant(v) => {
+          v.insert(pad);
+        }
+        Occupied(o) => {
+          let o = o.into_mut();
+          *o = *o + pad;
+        }
+      }
+    }
+  }
+
+  blended
+}
+
+// Writes a Markov chain or a FreqTable in a compact binary format
+pub fn save<S, W>(value: &S, writer: W) -> bincode::Result<()>
+where
+  S: Serialize,
+  W: Write,
+{
+  bincode::serialize_into(writer, value)
+}
+
+pub fn load<D, R>(reader: R) -> bincode::Result<D>
+where
+  D: DeserializeOwned,
+  R: Read,
+{
+  bincode::deserialize_from(reader)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::rngs::StdRng;
+  use std::collections::BTreeSet;
+
+  fn counts(word: &str) -> HashMap<char, usize> {
+    let mut ret = HashMap::new();
+
+    for c in word.chars() {
+      let n = ret.entry(c).or_insert(0);
+      *n = *n + 1;
+    }
+
+    ret
+  }
+
+  fn sorted(word: &str) -> Vec<char> {
+    let mut ret: Vec<char> = word.chars().collect();
+    ret.sort();
+    ret
+  }
+
+  // Shuffles each word starting from each of its letters, checking that the
+  // result uses up exactly the letters it was given
+  fn check(chain: &Markov<char>, words: &[&str]) {
+    for seed in 0..8 {
+      let mut rng = StdRng::from_seed([seed; 32]);
+
+      for word in words {
+        let remain = counts(word);
+        let firsts: BTreeSet<char> = word.chars().collect();
+
+        for first in firsts {
+          let out: String =
+            chain.iter_counted(&first, remain.clone(), &mut rng).collect();
+
+          assert_eq!(out.chars().next(), Some(first), "{} -> {}", word, out);
+          assert_eq!(sorted(&out), sorted(word), "{} -> {}", word, out);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn iter_counted_is_a_permutation() {
+    let corpus = ["banana", "bandana", "cabana", "abba"];
+
+    // Includes letters (x, z) and contexts (nn, aaa) the chain never saw
+    let words = [
+      "a", "b", "x", "aa", "ab", "banana", "aaaa", "nnab", "xzzy", "bazaar",
+      "abcxyzabc",
+    ];
+
+    for order in 1..5 {
+      let chain = Markov::new(analyze_corpus(
+        corpus.iter().map(|w| (1.0, w.chars())),
+        order,
+      ));
+
+      check(&chain, &words);
+    }
+  }
+
+  #[test]
+  fn iter_counted_with_empty_chain() {
+    let chain = Markov::new(FreqTable::new());
+
+    check(&chain, &["a", "aa", "abc", "zzyzx"]);
+  }
+
+  #[test]
+  fn iter_counted_with_nothing_left() {
+    let chain = Markov::new(FreqTable::new());
+    let mut rng = StdRng::from_seed([0; 32]);
+
+    // Nothing but the seed itself
+    let out: Vec<char> =
+      chain.iter_counted(&'a', counts("a"), &mut rng).collect();
+    assert_eq!(out, vec!['a']);
+
+    // An empty multiset leaves nothing to start from
+    let out: Vec<char> =
+      chain.iter_counted(&'a', HashMap::new(), &mut rng).collect();
+    assert_eq!(out, Vec::new());
+
+    let out: Vec<char> =
+      chain.iter_counted(&'a', counts("bc"), &mut rng).collect();
+    assert_eq!(out, Vec::new());
+  }
+
+  #[test]
+  fn save_and_load() {
+    let table = analyze_corpus(
+      ["banana", "çöğüş", "abba"].iter().map(|w| (1.5, w.chars())),
+      3,
+    );
+
+    let mut bytes = Vec::new();
+    save(&table, &mut bytes).unwrap();
+    let loaded: FreqTable<char> = load(&bytes[..]).unwrap();
+
+    assert_eq!(loaded, table);
+
+    let chain = Markov::new(table);
+
+    let mut bytes = Vec::new();
+    save(&chain, &mut bytes).unwrap();
+    let loaded: Markov<char> = load(&bytes[..]).unwrap();
+
+    assert_eq!(loaded.order(), chain.order());
+
+    for history in &["", "b", "an", "ban", "çö", "xyz"] {
+      let history: Vec<char> = history.chars().collect();
+
+      assert_eq!(
+        loaded.probabilities(&history),
+        chain.probabilities(&history)
+      );
+    }
+  }
+}
@@ -0,0 +1,395 @@
+use markov::{self, FreqTable, Markov};
+use rand::{Rng, RngCore, SeedableRng, StdRng};
+use std::collections::{HashMap, HashSet};
+
+// How many arrangements to try before settling for the least revealing one
+const HIDE_ATTEMPTS: usize = 200;
+
+// Words shorter than this are only avoided if they're in the current set
+pub const HIDE_MIN_LEN: usize = 4;
+
+// How much a set's own words count against the whole word list when building
+// the letter model for its round
+pub const SET_MODEL_WEIGHT: f64 = 0.5;
+
+// Longer words in a set count for more, since those are the ones to hide
+pub const SET_LEN_EXPONENT: f64 = 3.4;
+
+// How much of each letter's transitions are spread evenly over every letter
+pub const MODEL_SMOOTHING: f64 = 0.1;
+
+// Letters in rough order of how common they are in English
+const FREQUENCY_ORDER: &str = "etaoinshrdlcumwfgypbvkjxqz";
+
+pub trait ShuffleStrategy {
+  fn name(&self) -> &'static str;
+
+  fn shuffle(&self, letters: &str, rng: &mut RngCore) -> String;
+
+  // Strategies that don't need any randomness can hand back a plain function,
+  // which the word box keeps applying as letters are typed and removed
+  fn sorter(&self) -> Option<fn(&str) -> String> {
+    None
+  }
+}
+
+// Makes an Rng from a number, so the same seed always gives the same rounds
+// and shuffles
+pub fn seeded_rng(seed: u64) -> StdRng {
+  let mut bytes = <StdRng as SeedableRng>::Seed::default();
+
+  for i in 0..8 {
+    bytes[i] = (seed >> (i * 8)) as u8;
+  }
+
+  StdRng::from_seed(bytes)
+}
+
+// Builds one of each strategy for the current round, always in the same order
+pub fn strategies<'a>(
+  chain: &'a Markov<char>,
+  hider: Hider<'a>,
+) -> Vec<Box<ShuffleStrategy + 'a>> {
+  vec![
+    Box::new(MarkovShuffle(chain)),
+    Box::new(Uniform),
+    Box::new(hider),
+    Box::new(Alphabetical),
+    Box::new(Vowels),
+    Box::new(Frequency),
+  ]
+}
+
+// The name of each strategy, in the order strategies() builds them
+pub fn names() -> Vec<&'static str> {
+  let chain = Markov::new(FreqTable::new());
+  let dict = HashSet::new();
+
+  let names = strategies(&chain, Hider::new(&[], &dict, &chain))
+    .iter()
+    .map(|s| s.name())
+    .collect();
+
+  names
+}
+
+// Builds the letter model for a round from its set and the word list's model
+pub fn round_model(
+  set: &[String],
+  global: &FreqTable<char>,
+  order: usize,
+) -> FreqTable<char> {
+  let local = markov::analyze_corpus(
+    set
+      .iter()
+      .map(|s| ((s.len() as f64).powf(SET_LEN_EXPONENT), s.chars())),
+    order,
+  );
+  let chars: HashSet<_> = set.iter().flat_map(|s| s.chars()).collect();
+
+  markov::blend(
+    &[(SET_MODEL_WEIGHT, &local), (1.0 - SET_MODEL_WEIGHT, global)],
+    &chars,
+    MODEL_SMOOTHING,
+  )
+}
+
+pub fn markov<R>(letters: &str, markov: &Markov<char>, rng: &mut R) -> String
+where
+  R: Rng,
+{
+  use std::collections::hash_map::Entry::*;
+
+  let mut remain: HashMap<char, usize> = HashMap::new();
+
+  for chr in letters.chars() {
+    match remain.entry(chr) {
+      Vacant(v) => {
+        v.insert(1);
+      }
+      Occupied(o) => {
+        let o = o.into_mut();
+        *o = *o + 1;
+      }
+    }
+  }
+
+  // Start from any of the letters; iter_counted copes with ones the chain
+  // has never seen
+  let chars: Vec<char> = letters.chars().collect();
+
+  if chars.is_empty() {
+    return String::new();
+  }
+
+  let seed = chars[rng.gen_range(0, chars.len())];
+
+  markov.iter_counted(&seed, remain, rng).collect()
+}
+
+pub fn uniform<R>(letters: &str, rng: &mut R) -> String
+where
+  R: Rng,
+{
+  let mut chars: Vec<_> = letters.chars().collect();
+  rng.shuffle(&mut chars);
+  chars.into_iter().collect()
+}
+
+fn sort_by_key<K, F>(letters: &str, f: F) -> String
+where
+  K: Ord,
+  F: Fn(char) -> K,
+{
+  let mut chars: Vec<_> = letters.chars().collect();
+  chars.sort_by_key(|&c| (f(c), c));
+  chars.into_iter().collect()
+}
+
+fn alphabetical(letters: &str) -> String {
+  sort_by_key(letters, |_| ())
+}
+
+fn vowels(letters: &str) -> String {
+  sort_by_key(letters, |c| !"aeiou".contains(c))
+}
+
+fn frequency(letters: &str) -> String {
+  sort_by_key(letters, |c| FREQUENCY_ORDER.find(c))
+}
+
+pub struct MarkovShuffle<'a>(&'a Markov<char>);
+
+impl<'a> ShuffleStrategy for MarkovShuffle<'a> {
+  fn name(&self) -> &'static str {
+    "markov"
+  }
+
+  fn shuffle(&self, letters: &str, mut rng: &mut RngCore) -> String {
+    markov(letters, self.0, &mut rng)
+  }
+}
+
+pub struct Uniform;
+
+impl ShuffleStrategy for Uniform {
+  fn name(&self) -> &'static str {
+    "uniform"
+  }
+
+  fn shuffle(&self, letters: &str, mut rng: &mut RngCore) -> String {
+    uniform(letters, &mut rng)
+  }
+}
+
+pub struct Alphabetical;
+
+impl ShuffleStrategy for Alphabetical {
+  fn name(&self) -> &'static str {
+    "alphabetical"
+  }
+
+  fn shuffle(&self, letters: &str, _: &mut RngCore) -> String {
+    alphabetical(letters)
+  }
+
+  fn sorter(&self) -> Option<fn(&str) -> String> {
+    Some(alphabetical)
+  }
+}
+
+// Groups the vowels before the consonants
+pub struct Vowels;
+
+impl ShuffleStrategy for Vowels {
+  fn name(&self) -> &'static str {
+    "vowels"
+  }
+
+  fn shuffle(&self, letters: &str, _: &mut RngCore) -> String {
+    vowels(letters)
+  }
+
+  fn sorter(&self) -> Option<fn(&str) -> String> {
+    Some(vowels)
+  }
+}
+
+// Puts common letters first, so the rare ones stand out at the end
+pub struct Frequency;
+
+impl ShuffleStrategy for Frequency {
+  fn name(&self) -> &'static str {
+    "frequency"
+  }
+
+  fn shuffle(&self, letters: &str, _: &mut RngCore) -> String {
+    frequency(letters)
+  }
+
+  fn sorter(&self) -> Option<fn(&str) -> String> {
+    Some(frequency)
+  }
+}
+
+// Rejects arrangements that spell out a word from the current set, or any
+// reasonably long word from the dictionary
+pub struct Hider<'a> {
+  set: HashSet<&'a str>,
+  dict: &'a HashSet<String>,
+  chain: &'a Markov<char>,
+}
+
+impl<'a> Hider<'a> {
+  pub fn new<I>(
+    set: I,
+    dict: &'a HashSet<String>,
+    chain: &'a Markov<char>,
+  ) -> Self
+  where
+    I: IntoIterator<Item = &'a String>,
+  {
+    Self {
+      set: set.into_iter().map(|s| s.as_str()).collect(),
+      dict,
+      chain,
+    }
+  }
+
+  // Counts how many words can be read off of the arrangement
+  pub fn hits(&self, s: &str) -> usize {
+    let chars: Vec<char> = s.chars().collect();
+    let mut hits = 0;
+
+    for i in 0..chars.len() {
+      for j in i + 1..chars.len() + 1 {
+        let sub: String = chars[i..j].iter().collect();
+
+        if self.set.contains(sub.as_str())
+          || (j - i >= HIDE_MIN_LEN && self.dict.contains(&sub))
+        {
+          hits = hits + 1;
+        }
+      }
+    }
+
+    hits
+  }
+
+  // Tries Markov arrangements first, then uniform ones, and returns the first
+  // that hides everything, or the one that gives away the least
+  fn hide<R>(&self, letters: &str, rng: &mut R) -> String
+  where
+    R: Rng,
+  {
+    let mut best: Option<(usize, String)> = None;
+
+    for i in 0..HIDE_ATTEMPTS {
+      let attempt = if i < HIDE_ATTEMPTS / 2 {
+        markov(letters, self.chain, rng)
+      } else {
+        uniform(letters, rng)
+      };
+
+      let hits = self.hits(&attempt);
+
+      if best.as_ref().map_or(true, |&(b, _)| hits < b) {
+        best = Some((hits, attempt));
+      }
+
+      if hits == 0 {
+        break;
+      }
+    }
+
+    best.map_or_else(|| letters.to_string(), |(_, s)| s)
+  }
+}
+
+impl<'a> ShuffleStrategy for Hider<'a> {
+  fn name(&self) -> &'static str {
+    "hide"
+  }
+
+  fn shuffle(&self, letters: &str, mut rng: &mut RngCore) -> String {
+    self.hide(letters, &mut rng)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn sorted(s: &str) -> Vec<char> {
+    let mut ret: Vec<char> = s.chars().collect();
+    ret.sort();
+    ret
+  }
+
+  fn chain(set: &[String]) -> Markov<char> {
+    Markov::new(round_model(set, &FreqTable::new(), 2))
+  }
+
+  #[test]
+  fn hits() {
+    let set: Vec<String> = vec!["ab".into(), "ba".into()];
+    let dict: HashSet<String> = vec!["abba".to_string()].into_iter().collect();
+    let chain = chain(&set);
+    let hider = Hider::new(&set, &dict, &chain);
+
+    assert_eq!(hider.hits("aab"), 1);
+    assert_eq!(hider.hits("aba"), 2);
+    assert_eq!(hider.hits("abba"), 3);
+    assert_eq!(hider.hits("aabb"), 1);
+  }
+
+  #[test]
+  fn hide_leaves_no_set_word() {
+    let set: Vec<String> = vec!["stab", "bats", "tabs", "bat", "tab", "sat"]
+      .into_iter()
+      .map(|s| s.to_string())
+      .collect();
+    let dict: HashSet<String> = vec!["abets".to_string(), "beast".to_string()]
+      .into_iter()
+      .collect();
+    let chain = chain(&set);
+    let hider = Hider::new(&set, &dict, &chain);
+
+    for seed in 0..20 {
+      let mut rng = seeded_rng(seed);
+      let hidden = hider.hide("beast", &mut rng);
+
+      assert_eq!(sorted(&hidden), sorted("beast"));
+      assert_eq!(hider.hits(&hidden), 0, "{}", hidden);
+
+      for word in set.iter().chain(&dict) {
+        assert!(!hidden.contains(word.as_str()), "{} in {}", word, hidden);
+      }
+    }
+  }
+
+  // Every arrangement of these letters spells something, so once the attempts
+  // run out the least revealing one is used
+  #[test]
+  fn hide_settles_for_fewest_hits() {
+    let set: Vec<String> = vec!["ab".into(), "ba".into()];
+    let dict = HashSet::new();
+    let chain = chain(&set);
+    let hider = Hider::new(&set, &dict, &chain);
+
+    for seed in 0..20 {
+      let mut rng = seeded_rng(seed);
+      let hidden = hider.hide("aab", &mut rng);
+
+      assert_eq!(sorted(&hidden), sorted("aab"));
+      assert_eq!(hider.hits(&hidden), 1, "{}", hidden);
+    }
+
+    // Nothing at all can hide a single letter that's a word by itself
+    let set: Vec<String> = vec!["a".into()];
+    let chain = self::chain(&set);
+    let hider = Hider::new(&set, &dict, &chain);
+
+    assert_eq!(hider.hide("a", &mut seeded_rng(0)), "a");
+  }
+}
@@ -0,0 +1,67 @@
+use diesel::{insert_into, prelude::*, sqlite::SqliteConnection};
+
+// The stats database is written by the game itself rather than by gen-words,
+// so its schema lives here instead of in the generated schema module
+table! {
+  history (oid) {
+    oid -> Integer,
+    key -> Text,
+    len -> Integer,
+    solved -> Bool,
+  }
+}
+
+#[derive(Insertable)]
+#[table_name = "history"]
+struct HistoryEntry<'a> {
+  key: &'a str,
+  len: i32,
+  solved: bool,
+}
+
+pub struct Stats {
+  conn: SqliteConnection,
+}
+
+impl Stats {
+  pub fn new(url: &str) -> Self {
+    let conn = SqliteConnection::establish(url).unwrap();
+
+    conn
+      .execute(
+        "CREATE TABLE IF NOT EXISTS history (
+          oid    INTEGER PRIMARY KEY,
+          key    TEXT NOT NULL,
+          len    INTEGER NOT NULL,
+          solved BOOLEAN NOT NULL
+        )",
+      )
+      .unwrap();
+
+    Self { conn }
+  }
+
+  pub fn record(&self, key_str: &str, was_solved: bool) {
+    insert_into(history::table)
+      .values(&HistoryEntry {
+        key: key_str,
+        len: key_str.chars().count() as i32,
+        solved: was_solved,
+      })
+      .execute(&self.conn)
+      .unwrap();
+  }
+
+  // Returns the keys of the last `count` rounds played with the given length
+  pub fn recent_keys(&self, len_key: usize, count: usize) -> Vec<String> {
+    use self::history::dsl::*;
+
+    history
+      .select(key)
+      .filter(len.eq(len_key as i32))
+      .order(oid.desc())
+      .limit(count as i64)
+      .load::<String>(&self.conn)
+      .unwrap()
+  }
+}
@@ -0,0 +1,84 @@
+use nc;
+use std::cmp;
+use tui::prelude_internal::*;
+
+pub struct BonusBox {
+  coredata: ElementCoreData,
+  win: nc::WINDOW,
+  words: Vec<String>,
+  points: usize,
+  bonus_pair: i32,
+}
+
+impl BonusBox {
+  pub fn new(bonus_pair: i32) -> Self {
+    Self {
+      coredata: Default::default(),
+      win: nc::newwin(1, 1, 0, 0),
+      words: Vec::new(),
+      points: 0,
+      bonus_pair,
+    }
+  }
+
+  // NB: this will probably change the desired size, so the caller should
+  //     re-measure the UI afterwards
+  pub fn add(&mut self, word: String, points: usize) {
+    self.words.push(word);
+    self.points = self.points + points;
+    self.render();
+  }
+
+  fn header(&self) -> String {
+    format!("bonus: {}", self.points)
+  }
+}
+
+impl ElementCore for BonusBox {
+  fn get_coredata(&self) -> &ElementCoreData {
+    &self.coredata
+  }
+
+  fn get_coredata_mut(&mut self) -> &mut ElementCoreData {
+    &mut self.coredata
+  }
+
+  fn measure_impl(&mut self, _: MeasureSize) -> MeasureSize {
+    let width = self.words.iter().fold(self.header().chars().count(), |w, s| {
+      cmp::max(w, s.chars().count())
+    });
+
+    MeasureSize {
+      w: Some(width as i32 + 1),
+      h: Some(self.words.len() as i32 + 1),
+    }
+  }
+
+  fn arrange_impl(&mut self, space: Rect) {
+    // Leave a column of padding on the left
+    nc::wresize(
+      self.win,
+      cmp::max(1, space.size.h),
+      cmp::max(1, space.size.w - 1),
+    );
+    nc::mvwin(self.win, space.pos.y, space.pos.x + 1);
+  }
+
+  fn render_impl(&mut self) {
+    nc::werase(self.win);
+
+    nc::mvwaddstr(self.win, 0, 0, &self.header());
+
+    let pair = nc::COLOR_PAIR(self.bonus_pair as i16);
+
+    nc::wattr_on(self.win, pair);
+
+    for (i, word) in self.words.iter().enumerate() {
+      nc::mvwaddstr(self.win, i as i32 + 1, 0, word);
+    }
+
+    nc::wattr_off(self.win, pair);
+
+    nc::wrefresh(self.win);
+  }
+}
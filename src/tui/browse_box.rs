@@ -0,0 +1,132 @@
+use nc;
+use std::cmp;
+use tui::prelude_internal::*;
+
+pub struct BrowseBox {
+  coredata: ElementCoreData,
+  win: nc::WINDOW,
+  size: Size,
+  mode: String,
+  query: String,
+  results: Vec<String>,
+  scroll: usize,
+}
+
+impl BrowseBox {
+  pub fn new() -> Self {
+    Self {
+      coredata: Default::default(),
+      win: nc::newwin(1, 1, 0, 0),
+      size: Size { w: 1, h: 1 },
+      mode: String::new(),
+      query: String::new(),
+      results: Vec::new(),
+      scroll: 0,
+    }
+  }
+
+  pub fn set_query(&mut self, mode: &str, query: &str) {
+    self.mode = mode.to_string();
+    self.query = query.to_string();
+    self.render();
+  }
+
+  pub fn set_results(&mut self, results: Vec<String>) {
+    self.results = results;
+    self.scroll = 0;
+    self.render();
+  }
+
+  // Scrolls by whole columns of results
+  pub fn scroll_by(&mut self, by: isize) {
+    let max = self.ncols().saturating_sub(1);
+    let scroll = cmp::max(0, self.scroll as isize + by) as usize;
+
+    self.scroll = cmp::min(max, scroll);
+    self.render();
+  }
+
+  fn col_width(&self) -> usize {
+    let width = self
+      .results
+      .iter()
+      .fold(0, |w, r| cmp::max(w, r.chars().count()));
+
+    width + 2
+  }
+
+  fn col_height(&self) -> usize {
+    cmp::max(1, self.size.h - 2) as usize
+  }
+
+  fn ncols(&self) -> usize {
+    (self.results.len() + self.col_height() - 1) / self.col_height()
+  }
+
+  fn prompt(&self) -> String {
+    format!("{}: ", self.mode)
+  }
+}
+
+impl ElementCore for BrowseBox {
+  fn get_coredata(&self) -> &ElementCoreData {
+    &self.coredata
+  }
+
+  fn get_coredata_mut(&mut self) -> &mut ElementCoreData {
+    &mut self.coredata
+  }
+
+  fn measure_impl(&mut self, space: MeasureSize) -> MeasureSize {
+    space
+  }
+
+  fn arrange_impl(&mut self, space: Rect) {
+    self.size = Size {
+      w: cmp::max(1, space.size.w),
+      h: cmp::max(1, space.size.h),
+    };
+
+    nc::wresize(self.win, self.size.h, self.size.w);
+    nc::mvwin(self.win, space.pos.y, space.pos.x);
+  }
+
+  fn render_impl(&mut self) {
+    nc::werase(self.win);
+
+    nc::wattr_on(self.win, nc::A_BOLD());
+    nc::mvwaddstr(self.win, 0, 0, &self.prompt());
+    nc::wattr_off(self.win, nc::A_BOLD());
+    nc::waddstr(self.win, &self.query);
+
+    nc::mvwaddstr(self.win, 1, 0, &format!("{} result(s)", self.results.len()));
+
+    let col_width = self.col_width();
+    let col_height = self.col_height();
+    let visible = cmp::max(1, self.size.w as usize / col_width);
+
+    for (i, result) in self
+      .results
+      .iter()
+      .enumerate()
+      .skip(self.scroll * col_height)
+      .take(visible * col_height)
+    {
+      let col = i / col_height - self.scroll;
+      let row = i % col_height;
+
+      nc::mvwaddstr(self.win, row as i32 + 2, (col * col_width) as i32, result);
+    }
+
+    nc::wrefresh(self.win);
+
+    self.render_cur();
+  }
+
+  fn render_cur_impl(&mut self) {
+    let x = self.prompt().chars().count() + self.query.chars().count();
+
+    nc::wmove(self.win, 0, x as i32);
+    nc::wrefresh(self.win);
+  }
+}
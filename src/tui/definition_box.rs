@@ -0,0 +1,124 @@
+use nc;
+use std::cmp;
+use tui::prelude_internal::*;
+
+pub struct DefinitionBox {
+  coredata: ElementCoreData,
+  win: nc::WINDOW,
+  size: Size,
+  word: Option<String>,
+  defs: Vec<String>,
+}
+
+// Greedily wraps a string on whitespace, breaking words longer than a line
+fn wrap_str(s: &str, width: usize) -> Vec<String> {
+  let mut lines = Vec::new();
+  let mut line = String::new();
+
+  for word in s.split_whitespace() {
+    let mut word: Vec<char> = word.chars().collect();
+
+    if !line.is_empty() {
+      if line.chars().count() + word.len() + 1 <= width {
+        line.push(' ');
+        line.extend(word);
+        continue;
+      }
+
+      lines.push(line);
+      line = String::new();
+    }
+
+    while word.len() > width {
+      let rest = word.split_off(width);
+      lines.push(word.into_iter().collect());
+      word = rest;
+    }
+
+    line.extend(word);
+  }
+
+  if !line.is_empty() {
+    lines.push(line);
+  }
+
+  lines
+}
+
+impl DefinitionBox {
+  pub fn new() -> Self {
+    Self {
+      coredata: Default::default(),
+      win: nc::newwin(1, 1, 0, 0),
+      size: Size { w: 1, h: 1 },
+      word: None,
+      defs: Vec::new(),
+    }
+  }
+
+  pub fn set_word(&mut self, word: Option<String>, defs: Vec<String>) {
+    self.word = word;
+    self.defs = defs;
+    self.render();
+  }
+}
+
+impl ElementCore for DefinitionBox {
+  fn get_coredata(&self) -> &ElementCoreData {
+    &self.coredata
+  }
+
+  fn get_coredata_mut(&mut self) -> &mut ElementCoreData {
+    &mut self.coredata
+  }
+
+  fn measure_impl(&mut self, space: MeasureSize) -> MeasureSize {
+    space
+  }
+
+  fn arrange_impl(&mut self, space: Rect) {
+    // Leave a column of padding on the left
+    self.size = Size {
+      w: cmp::max(1, space.size.w - 1),
+      h: cmp::max(1, space.size.h),
+    };
+
+    nc::wresize(self.win, self.size.h, self.size.w);
+    nc::mvwin(self.win, space.pos.y, space.pos.x + 1);
+  }
+
+  fn render_impl(&mut self) {
+    nc::werase(self.win);
+
+    if let Some(ref word) = self.word {
+      let width = self.size.w as usize;
+      let mut y = 0;
+
+      nc::wattr_on(self.win, nc::A_BOLD());
+
+      for line in wrap_str(word, width) {
+        nc::mvwaddstr(self.win, y, 0, &line);
+        y = y + 1;
+      }
+
+      nc::wattr_off(self.win, nc::A_BOLD());
+
+      if self.defs.is_empty() {
+        nc::mvwaddstr(self.win, y, 0, "(no definition)");
+      }
+
+      for (i, def) in self.defs.iter().enumerate() {
+        if y >= self.size.h {
+          break;
+        }
+
+        for line in wrap_str(&format!("{}. {}", i + 1, def), width) {
+          nc::mvwaddstr(self.win, y, 0, &line);
+          y = y + 1;
+        }
+      }
+    }
+
+    nc::wrefresh(self.win);
+  }
+}
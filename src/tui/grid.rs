@@ -134,6 +134,26 @@ impl<'a> ElementCore for Grid<'a> {
       }
     }
 
+    // Static rows and columns that only cross non-static ones don't show up
+    // in static_cells, but they still need a size
+    for (i, row) in self.rows.iter().enumerate() {
+      match row {
+        Static(h) => {
+          row_sizes.insert(i, *h);
+        }
+        _ => (),
+      }
+    }
+
+    for (j, col) in self.cols.iter().enumerate() {
+      match col {
+        Static(w) => {
+          col_sizes.insert(j, *w);
+        }
+        _ => (),
+      }
+    }
+
     for (i, j) in content_cells {
       let cell = (i, j);
 
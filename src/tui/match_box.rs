@@ -6,6 +6,7 @@ pub enum MatchBoxStyle {
   Normal,
   Reveal,
   Highlight,
+  Select,
 }
 
 pub struct MatchBox {
@@ -16,10 +17,16 @@ pub struct MatchBox {
   style: MatchBoxStyle,
   reveal_pair: i32,
   hl_pair: i32,
+  select_pair: i32,
 }
 
 impl MatchBox {
-  pub fn new(form: WordlistForm, reveal_pair: i32, hl_pair: i32) -> Self {
+  pub fn new(
+    form: WordlistForm,
+    reveal_pair: i32,
+    hl_pair: i32,
+    select_pair: i32,
+  ) -> Self {
     Self {
       coredata: Default::default(),
       win: nc::newwin(1, 1, 0, 0),
@@ -28,6 +35,7 @@ impl MatchBox {
       style: MatchBoxStyle::Normal,
       reveal_pair,
       hl_pair,
+      select_pair,
     }
   }
 
@@ -71,13 +79,13 @@ impl ElementCore for MatchBox {
 
   fn measure_impl(&mut self, _: MeasureSize) -> MeasureSize {
     MeasureSize {
-      w: Some(self.displayed_str().len() as i32),
+      w: Some(self.displayed_str().chars().count() as i32),
       h: Some(1),
     }
   }
 
   fn arrange_impl(&mut self, space: Rect) {
-    nc::wresize(self.win, 1, self.form.full.len() as i32);
+    nc::wresize(self.win, 1, self.form.full.chars().count() as i32);
     nc::mvwin(self.win, space.pos.y, space.pos.x);
   }
 
@@ -88,6 +96,7 @@ impl ElementCore for MatchBox {
       Normal => None,
       Reveal => Some(self.reveal_pair),
       Highlight => Some(self.hl_pair),
+      Select => Some(self.select_pair),
     }.map(|p| nc::COLOR_PAIR(p as i16));
 
     if let Some(pair) = pair {
@@ -2,8 +2,12 @@ pub mod core;
 pub mod element;
 pub mod internal;
 
+mod bonus_box;
+mod browse_box;
+mod definition_box;
 mod grid;
 mod match_box;
+mod status_box;
 mod test_view;
 mod ui_root;
 mod word_box;
@@ -15,6 +19,7 @@ pub mod prelude_internal {
 
 pub mod controls {
   pub use super::{
-    grid::*, match_box::*, test_view::*, ui_root::*, word_box::*, wrap_box::*,
+    bonus_box::*, browse_box::*, definition_box::*, grid::*, match_box::*,
+    status_box::*, test_view::*, ui_root::*, word_box::*, wrap_box::*,
   };
 }
@@ -0,0 +1,58 @@
+use nc;
+use std::cmp;
+use tui::prelude_internal::*;
+
+// A single line of text about the current round, such as the shuffle strategy
+pub struct StatusBox {
+  coredata: ElementCoreData,
+  win: nc::WINDOW,
+  text: String,
+}
+
+impl StatusBox {
+  pub fn new() -> Self {
+    Self {
+      coredata: Default::default(),
+      win: nc::newwin(1, 1, 0, 0),
+      text: String::new(),
+    }
+  }
+
+  pub fn set_text(&mut self, text: String) {
+    if self.text == text {
+      return;
+    }
+
+    self.text = text;
+    self.render();
+  }
+}
+
+impl ElementCore for StatusBox {
+  fn get_coredata(&self) -> &ElementCoreData {
+    &self.coredata
+  }
+
+  fn get_coredata_mut(&mut self) -> &mut ElementCoreData {
+    &mut self.coredata
+  }
+
+  fn measure_impl(&mut self, _: MeasureSize) -> MeasureSize {
+    MeasureSize {
+      w: Some(self.text.chars().count() as i32 + 1),
+      h: Some(1),
+    }
+  }
+
+  fn arrange_impl(&mut self, space: Rect) {
+    // Leave a column of padding on the left
+    nc::wresize(self.win, 1, cmp::max(1, space.size.w - 1));
+    nc::mvwin(self.win, space.pos.y, space.pos.x + 1);
+  }
+
+  fn render_impl(&mut self) {
+    nc::werase(self.win);
+    nc::mvwaddstr(self.win, 0, 0, &self.text);
+    nc::wrefresh(self.win);
+  }
+}
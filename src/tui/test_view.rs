@@ -7,13 +7,29 @@ pub struct TestView<'a> {
 }
 
 impl<'a> TestView<'a> {
-  pub fn new(word_box: ElemRef<'a>, match_box: ElemRef<'a>) -> Self {
+  pub fn new(
+    word_box: ElemRef<'a>,
+    match_box: ElemRef<'a>,
+    bonus_box: ElemRef<'a>,
+    definition_box: ElemRef<'a>,
+    status_box: ElemRef<'a>,
+  ) -> Self {
     Self {
       coredata: Default::default(),
       grid: el::wrap(Grid::new(
-        vec![(word_box.clone(), (1, 0)), (match_box, (0, 0))],
+        vec![
+          (word_box.clone(), (1, 0)),
+          (match_box, (0, 0)),
+          (bonus_box, (0, 1)),
+          (definition_box, (0, 2)),
+          (status_box, (1, 2)),
+        ],
         vec![GridLength::Dynamic(1.0), GridLength::Content],
-        vec![GridLength::Dynamic(1.0)],
+        vec![
+          GridLength::Dynamic(1.0),
+          GridLength::Content,
+          GridLength::Static(32),
+        ],
       )),
       word_box,
     }
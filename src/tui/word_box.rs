@@ -1,8 +1,51 @@
-use markov::Markov;
 use nc;
-use std::{cmp, collections::HashMap};
+use std::cmp;
 use tui::prelude_internal::*;
 
+// Everything about the word box that undo and redo put back
+#[derive(Clone)]
+struct Snapshot {
+  cur: usize,
+  buf: String,
+  ghost_buf: String,
+  sorter: Option<fn(&str) -> String>,
+  strategy: usize,
+}
+
+impl PartialEq for Snapshot {
+  fn eq(&self, other: &Self) -> bool {
+    self.cur == other.cur
+      && self.buf == other.buf
+      && self.ghost_buf == other.ghost_buf
+      && self.sorter.map(|f| f as usize) == other.sorter.map(|f| f as usize)
+      && self.strategy == other.strategy
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum InputMode {
+  Free,   // Letters that aren't left are quietly ignored
+  Strict, // Letters that aren't left flash the screen
+  Tiles,  // Like Strict, but tiles can also be picked by number or selection
+}
+
+// Tiles and the cursor count letters rather than bytes, so this finds where
+// the letter at `at` starts, or the end of the string if it's past the last one
+fn byte_at(s: &str, at: usize) -> usize {
+  s.char_indices().nth(at).map_or(s.len(), |(i, _)| i)
+}
+
+impl InputMode {
+  pub fn parse(s: &str) -> Option<Self> {
+    match s {
+      "free" => Some(InputMode::Free),
+      "strict" => Some(InputMode::Strict),
+      "tiles" => Some(InputMode::Tiles),
+      _ => None,
+    }
+  }
+}
+
 pub struct WordBox {
   coredata: ElementCoreData,
   win: nc::WINDOW,
@@ -10,7 +53,12 @@ pub struct WordBox {
   buf: String,
   ghost_buf: String,
   bad: bool,
-  auto_sort: bool,
+  sorter: Option<fn(&str) -> String>,
+  strategy: usize, // The shuffle strategy last applied, by its index
+  undo: Vec<Snapshot>,
+  redo: Vec<Snapshot>,
+  mode: InputMode,
+  tile: usize, // The selected ghost tile, in tile mode
   key: String,
   ghost_pair: i32,
   bad_ghost_pair: i32,
@@ -20,6 +68,8 @@ pub struct WordBox {
 impl WordBox {
   pub fn new(
     key: String,
+    mode: InputMode,
+    strategy: usize,
     ghost_pair: i32,
     bad_ghost_pair: i32,
     auto_ghost_pair: i32,
@@ -33,7 +83,12 @@ impl WordBox {
       buf: String::new(),
       ghost_buf,
       bad: false,
-      auto_sort: false,
+      sorter: None,
+      strategy,
+      undo: Vec::new(),
+      redo: Vec::new(),
+      mode,
+      tile: 0,
       key,
       ghost_pair,
       bad_ghost_pair,
@@ -45,6 +100,10 @@ impl WordBox {
     &self.buf
   }
 
+  pub fn mode(&self) -> InputMode {
+    self.mode
+  }
+
   pub fn set_bad(&mut self, val: bool) {
     if self.bad == val {
       return;
@@ -56,32 +115,88 @@ impl WordBox {
   }
 
   pub fn auto_sort(&self) -> bool {
-    self.auto_sort
+    self.sorter.is_some()
   }
 
-  pub fn set_auto_sort(&mut self, val: bool) {
-    self.auto_sort = val;
+  pub fn strategy(&self) -> usize {
+    self.strategy
+  }
+
+  // Keeps the unused letters arranged by the given strategy's function until
+  // it's turned off again
+  pub fn set_sorter(&mut self, strategy: usize, sorter: fn(&str) -> String) {
+    let before = self.snapshot();
+
+    self.sorter = Some(sorter);
+    self.strategy = strategy;
 
     self.fix_ghost();
+    self.commit(before);
     self.render();
   }
 
+  fn snapshot(&self) -> Snapshot {
+    Snapshot {
+      cur: self.cur,
+      buf: self.buf.clone(),
+      ghost_buf: self.ghost_buf.clone(),
+      sorter: self.sorter,
+      strategy: self.strategy,
+    }
+  }
+
+  fn restore(&mut self, snapshot: Snapshot) {
+    self.cur = snapshot.cur;
+    self.buf = snapshot.buf;
+    self.ghost_buf = snapshot.ghost_buf;
+    self.sorter = snapshot.sorter;
+    self.strategy = snapshot.strategy;
+    self.bad = false;
+    self.clamp_tile();
+  }
+
+  // Adds the state from before an edit to the undo history, if the edit
+  // actually changed anything
+  fn commit(&mut self, before: Snapshot) {
+    if self.snapshot() != before {
+      self.undo.push(before);
+      self.redo.clear();
+    }
+  }
+
+  pub fn undo(&mut self) {
+    if let Some(snapshot) = self.undo.pop() {
+      let current = self.snapshot();
+      self.redo.push(current);
+      self.restore(snapshot);
+      self.render();
+    }
+  }
+
+  pub fn redo(&mut self) {
+    if let Some(snapshot) = self.redo.pop() {
+      let current = self.snapshot();
+      self.undo.push(current);
+      self.restore(snapshot);
+      self.render();
+    }
+  }
+
   fn fix_ghost(&mut self) {
-    if self.auto_sort {
-      let mut chars: Vec<_> = self.ghost_buf.chars().collect();
-      chars.sort();
-      self.ghost_buf = chars.into_iter().collect();
+    if let Some(sorter) = self.sorter {
+      self.ghost_buf = sorter(&self.ghost_buf);
     }
   }
 
   fn remove(&mut self, at: usize) {
+    let at = byte_at(&self.buf, at);
     self.ghost_buf.insert(0, self.buf.remove(at));
     self.fix_ghost();
   }
 
   fn del_empty(&mut self) {
-    if self.auto_sort {
-      self.auto_sort = false;
+    if self.sorter.is_some() {
+      self.sorter = None;
       self.render();
     } else if self.bad {
       self.bad = false;
@@ -90,6 +205,8 @@ impl WordBox {
   }
 
   pub fn del_left(&mut self) {
+    let before = self.snapshot();
+
     if self.buf.is_empty() {
       self.del_empty();
     } else {
@@ -98,64 +215,152 @@ impl WordBox {
         let cur = self.cur;
         self.remove(cur);
         self.render();
-      } else if self.buf.len() == 1 {
+      } else if self.buf.chars().count() == 1 {
         self.remove(0);
         self.render();
       }
     }
+
+    self.commit(before);
   }
 
   pub fn del_right(&mut self) {
+    let before = self.snapshot();
+
     if self.buf.is_empty() {
       self.del_empty();
     } else {
-      if self.cur < self.buf.len() {
+      if self.cur < self.buf.chars().count() {
         let cur = self.cur;
         self.remove(cur);
         self.render();
       }
     }
+
+    self.commit(before);
+  }
+
+  // Moves every letter in the word back into the unused letters
+  fn take_back(&mut self) {
+    self.ghost_buf.insert_str(0, &self.buf);
+    self.buf.clear();
+    self.cur = 0;
+    self.fix_ghost();
   }
 
   pub fn clear(&mut self) {
+    let before = self.snapshot();
+
     if self.buf.is_empty() {
       // TODO: move this block elsewhere probably
       self.del_empty();
     }
-    self.ghost_buf.insert_str(0, &self.buf);
-    self.buf.clear();
-    self.cur = 0;
-    self.fix_ghost();
+    self.take_back();
+    self.render();
+
+    self.commit(before);
+  }
+
+  // Turns off sorting and clears the word, as a single step to undo
+  pub fn reset(&mut self) {
+    let before = self.snapshot();
+
+    self.sorter = None;
+    self.take_back();
     self.render();
+
+    self.commit(before);
   }
 
   pub fn put(&mut self, s: &str) {
+    let before = self.snapshot();
+
     let mut dirty = false;
+    let mut refused = false;
+
+    let key_len = self.key.chars().count();
 
     for c in s.chars() {
-      if self.buf.len() >= self.key.len() {
+      if self.buf.chars().count() >= key_len {
         break;
       }
 
       match self.ghost_buf.find(c) {
         Some(i) => {
           dirty = true;
-          self.buf.insert(self.cur, c);
+          let cur = byte_at(&self.buf, self.cur);
+          self.buf.insert(cur, c);
           self.cur = self.cur + 1;
           self.ghost_buf.remove(i);
         }
-        None => {}
+        None => refused = true,
       }
     }
 
+    if refused {
+      self.refuse();
+    }
+
     if dirty {
       self.fix_ghost();
+      self.clamp_tile();
+      self.render();
+    }
+
+    self.commit(before);
+  }
+
+  // Moves the ghost tile at the given position into the word at the cursor
+  pub fn pick(&mut self, at: usize) {
+    if at >= self.ghost_buf.chars().count() {
+      self.refuse();
+      return;
+    }
+
+    let before = self.snapshot();
+
+    let at = byte_at(&self.ghost_buf, at);
+    let c = self.ghost_buf.remove(at);
+    let cur = byte_at(&self.buf, self.cur);
+    self.buf.insert(cur, c);
+    self.cur = self.cur + 1;
+
+    self.fix_ghost();
+    self.clamp_tile();
+    self.commit(before);
+    self.render();
+  }
+
+  pub fn pick_selected(&mut self) {
+    let tile = self.tile;
+    self.pick(tile);
+  }
+
+  pub fn select_tile_by(&mut self, by: isize) {
+    let len = self.ghost_buf.chars().count() as isize;
+
+    if len > 0 {
+      let by = by % len + len; // Keep it positive for wrapping around
+
+      self.tile = ((self.tile as isize + by) % len) as usize;
       self.render();
     }
   }
 
+  fn clamp_tile(&mut self) {
+    let len = self.ghost_buf.chars().count();
+
+    self.tile = cmp::min(self.tile, len.saturating_sub(1));
+  }
+
+  fn refuse(&self) {
+    if self.mode != InputMode::Free {
+      nc::flash();
+    }
+  }
+
   pub fn move_to(&mut self, to: usize) {
-    self.cur = cmp::max(0, cmp::min(self.buf.len(), to));
+    self.cur = cmp::max(0, cmp::min(self.buf.chars().count(), to));
     self.render_cur();
   }
 
@@ -177,45 +382,52 @@ impl WordBox {
   }
 
   pub fn end(&mut self) {
-    let pos = self.buf.len();
+    let pos = self.buf.chars().count();
     self.move_to(pos);
   }
 
-  pub fn shuffle(&mut self, markov: &Markov<char>) {
-    use std::collections::hash_map::Entry::*;
-
-    self.auto_sort = false;
-
-    let mut remain: HashMap<char, usize> = HashMap::new();
-
-    for chr in self.ghost_buf.chars() {
-      match remain.entry(chr) {
-        Vacant(v) => {
-          v.insert(1);
-        }
-        Occupied(o) => {
-          let o = o.into_mut();
-          *o = *o + 1;
-        }
+  // Rearranges the unused letters with the given strategy's function, which
+  // must return the same letters it was given
+  pub fn shuffle<F>(&mut self, strategy: usize, f: F)
+  where
+    F: FnOnce(&str) -> String,
+  {
+    let before = self.snapshot();
+
+    self.sorter = None;
+    self.strategy = strategy;
+
+    let shuffled = f(&self.ghost_buf);
+
+    debug_assert_eq!(
+      {
+        let mut c: Vec<_> = shuffled.chars().collect();
+        c.sort();
+        c
+      },
+      {
+        let mut c: Vec<_> = self.ghost_buf.chars().collect();
+        c.sort();
+        c
       }
-    }
+    );
 
-    // TODO: keep an eye on this, it doesn't seem correct
-    self.ghost_buf = markov
-      .iter_counted(
-        markov
-          .rand_seed()
-          .filter(|c| self.ghost_buf.contains(**c))
-          .next()
-          .unwrap(),
-        remain,
-      )
-      .collect();
+    self.ghost_buf = shuffled;
 
+    self.commit(before);
     self.render();
   }
 }
 
+impl WordBox {
+  fn rows(&self) -> i32 {
+    match self.mode {
+      InputMode::Tiles => 2,
+      _ => 1,
+    }
+  }
+}
+
 impl ElementCore for WordBox {
   fn get_coredata(&self) -> &ElementCoreData {
     &self.coredata
@@ -227,22 +439,26 @@ impl ElementCore for WordBox {
 
   fn measure_impl(&mut self, _: MeasureSize) -> MeasureSize {
     MeasureSize {
-      w: Some(self.key.len() as i32 * 2 + 1),
-      h: Some(1),
+      w: Some(self.key.chars().count() as i32 * 2 + 1),
+      h: Some(self.rows()),
     }
   }
 
   fn arrange_impl(&mut self, space: Rect) {
-    nc::wresize(self.win, 1, self.key.len() as i32 * 2 - 1);
+    let key_len = self.key.chars().count() as i32;
+
+    nc::wresize(self.win, self.rows(), key_len * 2 - 1);
     nc::mvwin(self.win, space.pos.y, space.pos.x);
   }
 
   fn render_impl(&mut self) {
-    for (i, ch) in self.buf.char_indices() {
+    nc::werase(self.win);
+
+    for (i, ch) in self.buf.chars().enumerate() {
       nc::mvwaddch(self.win, 0, (i * 2) as i32, ch as u32);
     }
 
-    let pair = nc::COLOR_PAIR(if self.auto_sort {
+    let pair = nc::COLOR_PAIR(if self.sorter.is_some() {
       self.auto_ghost_pair
     } else {
       if self.bad {
@@ -254,14 +470,35 @@ impl ElementCore for WordBox {
 
     nc::wattr_on(self.win, pair);
 
-    let buf_len = self.buf.len();
+    let buf_len = self.buf.chars().count();
 
-    for (i, ch) in self.ghost_buf.char_indices() {
-      nc::mvwaddch(self.win, 0, ((i + buf_len) * 2) as i32, ch as u32);
+    for (i, ch) in self.ghost_buf.chars().enumerate() {
+      let x = ((i + buf_len) * 2) as i32;
+      let selected = self.mode == InputMode::Tiles && i == self.tile;
+
+      if selected {
+        nc::wattr_on(self.win, nc::A_REVERSE());
+      }
+
+      nc::mvwaddch(self.win, 0, x, ch as u32);
+
+      if selected {
+        nc::wattr_off(self.win, nc::A_REVERSE());
+      }
     }
 
     nc::wattr_off(self.win, pair);
 
+    // Number each tile with the key that picks it
+    if self.mode == InputMode::Tiles {
+      for i in 0..self.ghost_buf.chars().count() {
+        let label = ((i + 1) % 10).to_string();
+        let x = ((i + buf_len) * 2) as i32;
+
+        nc::mvwaddstr(self.win, 1, x, &label);
+      }
+    }
+
     nc::wrefresh(self.win);
 
     self.render_cur();
@@ -272,3 +509,147 @@ impl ElementCore for WordBox {
     nc::wrefresh(self.win);
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn word_box(key: &str) -> WordBox {
+    WordBox::new(key.into(), InputMode::Tiles, 0, 1, 2, 3)
+  }
+
+  fn sort(s: &str) -> String {
+    let mut chars: Vec<char> = s.chars().collect();
+    chars.sort();
+    chars.into_iter().collect()
+  }
+
+  fn reverse(s: &str) -> String {
+    s.chars().rev().collect()
+  }
+
+  #[test]
+  fn undo_reset_in_one_step() {
+    let mut w = word_box("cab");
+
+    w.put("ab");
+    w.set_sorter(3, sort);
+    assert_eq!(w.ghost_buf, "c");
+
+    // A match clears the word and turns off sorting together
+    w.reset();
+    assert_eq!(w.buf(), "");
+    assert_eq!(w.ghost_buf, "abc");
+    assert!(!w.auto_sort());
+
+    w.undo();
+    assert_eq!(w.buf(), "ab");
+    assert_eq!(w.ghost_buf, "c");
+    assert!(w.auto_sort());
+    assert_eq!(w.strategy(), 3);
+
+    w.undo();
+    assert_eq!(w.buf(), "ab");
+    assert!(!w.auto_sort());
+    assert_eq!(w.strategy(), 0);
+
+    w.redo();
+    w.redo();
+    assert_eq!(w.buf(), "");
+    assert!(!w.auto_sort());
+    assert_eq!(w.strategy(), 3);
+  }
+
+  #[test]
+  fn undo_restores_strategy() {
+    let mut w = word_box("abc");
+
+    w.shuffle(1, reverse);
+    assert_eq!(w.ghost_buf, "cba");
+    assert_eq!(w.strategy(), 1);
+
+    w.set_sorter(4, sort);
+    assert_eq!(w.ghost_buf, "abc");
+    assert_eq!(w.strategy(), 4);
+
+    w.undo();
+    assert_eq!(w.ghost_buf, "cba");
+    assert_eq!(w.strategy(), 1);
+    assert!(!w.auto_sort());
+
+    w.undo();
+    assert_eq!(w.ghost_buf, "abc");
+    assert_eq!(w.strategy(), 0);
+  }
+
+  #[test]
+  fn pick_multibyte_tiles() {
+    let mut w = word_box("éa");
+
+    w.pick(1);
+    assert_eq!(w.buf(), "a");
+    assert_eq!(w.ghost_buf, "é");
+
+    // Past the last tile, counting letters rather than bytes
+    w.pick(1);
+    assert_eq!(w.buf(), "a");
+    assert_eq!(w.ghost_buf, "é");
+
+    w.pick(0);
+    assert_eq!(w.buf(), "aé");
+    assert_eq!(w.ghost_buf, "");
+    assert_eq!(w.cur, 2);
+
+    w.left();
+    w.del_left();
+    assert_eq!(w.buf(), "é");
+    assert_eq!(w.cur, 0);
+
+    w.end();
+    assert_eq!(w.cur, 1);
+
+    w.undo();
+    assert_eq!(w.buf(), "aé");
+  }
+
+  #[test]
+  fn put_multibyte_letters() {
+    let mut w = word_box("éaé");
+
+    w.put("éé");
+    assert_eq!(w.buf(), "éé");
+    assert_eq!(w.ghost_buf, "a");
+
+    // Only the letters left can be typed
+    w.put("é");
+    assert_eq!(w.buf(), "éé");
+
+    w.home();
+    w.right();
+    w.put("a");
+    assert_eq!(w.buf(), "éaé");
+    assert_eq!(w.cur, 2);
+    assert_eq!(w.ghost_buf, "");
+
+    w.del_right();
+    assert_eq!(w.buf(), "éa");
+    assert_eq!(w.ghost_buf, "é");
+  }
+
+  #[test]
+  fn select_multibyte_tiles() {
+    let mut w = word_box("ééa");
+
+    w.select_tile_by(-1);
+    assert_eq!(w.tile, 2);
+
+    w.pick_selected();
+    assert_eq!(w.buf(), "a");
+
+    // The selection stays on a tile that's still there
+    assert_eq!(w.tile, 1);
+
+    w.select_tile_by(3);
+    assert_eq!(w.tile, 0);
+  }
+}
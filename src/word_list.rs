@@ -1,5 +1,12 @@
 use diesel::{prelude::*, sqlite::SqliteConnection};
+use markov::FreqTable;
 use models::*;
+use rand::Rng;
+use std::collections::HashMap;
+
+// Each avoided key is bound as its own parameter, and SQLite only allows so
+// many in one statement
+pub static MAX_AVOID: usize = 500;
 
 pub struct WordList {
   conn: SqliteConnection,
@@ -48,20 +55,72 @@ impl WordList {
       .collect()
   }
 
-  pub fn get_set_keys(&self, len_key: &usize) -> Vec<String> {
+  // Returns every word in the list with at least `min_len` letters
+  pub fn get_words(&self, min_len: usize) -> Vec<String> {
+    use schema::form_ids::dsl::*;
+
+    form_ids
+      .select(norm)
+      .load::<String>(&self.conn)
+      .unwrap()
+      .into_iter()
+      .filter(|n| n.chars().count() >= min_len)
+      .collect()
+  }
+
+  // Loads the letter model gen-words built from the whole word list, up to
+  // contexts of `order` letters
+  pub fn get_transitions(&self, order: usize) -> FreqTable<char> {
+    use std::collections::hash_map::Entry::*;
+
     let results = {
-      use schema::set_keys::dsl::*;
+      use schema::transitions::dsl::*;
 
-      set_keys
-        .filter(len.eq(*len_key as i32))
-        .load::<SetKeyQ>(&self.conn)
-        .unwrap()
+      transitions.load::<TransitionQ>(&self.conn).unwrap()
     };
 
-    results.into_iter().map(|r| r.key).collect()
+    let mut table = FreqTable::new();
+
+    for result in results {
+      let context: Vec<char> = result.context.chars().collect();
+
+      if context.len() > order {
+        continue;
+      }
+
+      let next = match result.next.chars().next() {
+        Some(c) => c,
+        None => continue,
+      };
+
+      match table.entry(context) {
+        Vacant(v) => v.insert(HashMap::new()),
+        Occupied(o) => o.into_mut(),
+      }.insert(next, result.weight);
+    }
+
+    table
   }
 
-  pub fn get_set(&self, key_str: &str) -> Vec<String> {
+  pub fn find_words(&self, pattern: &WordPattern) -> Vec<String> {
+    let results = {
+      use schema::form_ids::dsl::*;
+
+      let mut query = form_ids.select(norm).order(norm).into_boxed();
+
+      for like in pattern.like_patterns() {
+        query = query.filter(norm.like(like));
+      }
+
+      query.load::<String>(&self.conn).unwrap()
+    };
+
+    // LIKE is case-insensitive and doesn't understand letter counts, so
+    // double-check everything it returned
+    results.into_iter().filter(|w| pattern.matches(w)).collect()
+  }
+
+  fn get_set_id(&self, key_str: &str) -> Option<i32> {
     let id_results = {
       use schema::set_ids::dsl::*;
 
@@ -74,7 +133,49 @@ impl WordList {
 
     assert!(id_results.len() <= 1);
 
-    let id_key = match id_results.first() {
+    id_results.first().map(|i| i.id)
+  }
+
+  // The key is picked by offset rather than with RANDOM() so that the choice
+  // is reproducible from the RNG
+  pub fn random_set_key<R>(
+    &self,
+    len_key: usize,
+    filters: &KeyFilters,
+    rng: &mut R,
+  ) -> Option<String>
+  where
+    R: Rng,
+  {
+    let mut results = {
+      use schema::set_keys::dsl::*;
+
+      let query = || {
+        set_keys
+          .filter(len.eq(len_key as i32))
+          .filter(key.ne_all(filters.avoid.iter().take(MAX_AVOID)))
+      };
+
+      let count: i64 = query().count().get_result(&self.conn).unwrap();
+
+      if count == 0 {
+        return None;
+      }
+
+      query()
+        .select(key)
+        .order(oid)
+        .offset(rng.gen_range(0, count))
+        .limit(1)
+        .load::<String>(&self.conn)
+        .unwrap()
+    };
+
+    results.pop()
+  }
+
+  pub fn get_set(&self, key_str: &str) -> Vec<String> {
+    let id_key = match self.get_set_id(key_str) {
       None => return Vec::new(),
       Some(i) => i,
     };
@@ -83,16 +184,226 @@ impl WordList {
       use schema::sets::dsl::*;
 
       sets
-        .filter(id.eq(id_key.id))
+        .filter(id.eq(id_key))
         .load::<SetQ>(&self.conn)
         .unwrap()
     };
 
     set_results.into_iter().map(|r| r.norm).collect()
   }
+
+  pub fn get_bonus(&self, key_str: &str) -> Vec<String> {
+    let id_key = match self.get_set_id(key_str) {
+      None => return Vec::new(),
+      Some(i) => i,
+    };
+
+    let bonus_results = {
+      use schema::bonuses::dsl::*;
+
+      bonuses
+        .filter(id.eq(id_key))
+        .load::<BonusQ>(&self.conn)
+        .unwrap()
+    };
+
+    bonus_results.into_iter().map(|r| r.norm).collect()
+  }
+
+  pub fn get_definitions(&self, key: &str) -> Vec<String> {
+    let results = {
+      use schema::definitions::dsl::*;
+
+      definitions
+        .filter(norm.eq(key))
+        .order(oid)
+        .load::<DefinitionQ>(&self.conn)
+        .unwrap()
+    };
+
+    results.into_iter().map(|r| r.definition).collect()
+  }
+}
+
+#[derive(Default)]
+pub struct KeyFilters {
+  pub avoid: Vec<String>, // Keys that shouldn't be picked, up to MAX_AVOID
 }
 
 pub struct WordlistForm {
   pub full: String,
   pub blanked: String,
 }
+
+pub enum WordPattern {
+  Prefix(String),
+  Suffix(String),
+  Blanks(String),   // Each _ matches exactly one letter
+  Contains(String), // Matches words containing at least these letters
+}
+
+fn count_chars(s: &str) -> HashMap<char, usize> {
+  let mut ret = HashMap::new();
+
+  for c in s.chars() {
+    use std::collections::hash_map::Entry::*;
+
+    match ret.entry(c) {
+      Occupied(o) => {
+        let val = o.into_mut();
+        *val = *val + 1;
+      }
+      Vacant(v) => {
+        v.insert(1);
+      }
+    }
+  }
+
+  ret
+}
+
+impl WordPattern {
+  // Patterns are matched against normalized words, so anything that isn't a
+  // letter (or a blank) can't match and is dropped
+  fn clean(s: &str, blanks: bool) -> String {
+    s.chars()
+      .filter(|c| c.is_alphanumeric() || (blanks && *c == '_'))
+      .flat_map(|c| c.to_lowercase())
+      .collect()
+  }
+
+  fn like_patterns(&self) -> Vec<String> {
+    use self::WordPattern::*;
+
+    match self {
+      Prefix(s) => vec![format!("{}%", Self::clean(s, false))],
+      Suffix(s) => vec![format!("%{}", Self::clean(s, false))],
+      Blanks(s) => vec![Self::clean(s, true)],
+      Contains(s) => count_chars(&Self::clean(s, false))
+        .keys()
+        .map(|c| format!("%{}%", c))
+        .collect(),
+    }
+  }
+
+  pub fn matches(&self, word: &str) -> bool {
+    use self::WordPattern::*;
+
+    match self {
+      Prefix(s) => word.starts_with(&Self::clean(s, false)),
+      Suffix(s) => word.ends_with(&Self::clean(s, false)),
+      Blanks(s) => {
+        let s = Self::clean(s, true);
+
+        s.chars().count() == word.chars().count()
+          && s.chars().zip(word.chars()).all(|(p, c)| p == '_' || p == c)
+      }
+      Contains(s) => {
+        let counts = count_chars(word);
+
+        count_chars(&Self::clean(s, false))
+          .iter()
+          .all(|(c, n)| n <= counts.get(c).unwrap_or(&0))
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::WordPattern::*;
+  use super::*;
+  use diesel::{connection::SimpleConnection, insert_into};
+  use schema::form_ids::dsl::*;
+
+  // Sorted the way SQLite sorts them, by code point
+  static WORDS: [&str; 14] = [
+    "ab", "abbot", "about", "aé", "ağaç", "banana", "bat", "stab", "tab",
+    "tabby", "tabbé", "çay", "çağ", "éa",
+  ];
+
+  fn word_list() -> WordList {
+    let list = WordList::new(":memory:");
+
+    let sql = "CREATE TABLE form_ids (norm TEXT PRIMARY KEY, id INTEGER)";
+    list.conn.batch_execute(sql).unwrap();
+
+    for (i, word) in WORDS.iter().enumerate() {
+      insert_into(form_ids)
+        .values((norm.eq(word), id.eq(i as i32)))
+        .execute(&list.conn)
+        .unwrap();
+    }
+
+    list
+  }
+
+  fn matching(pattern: &WordPattern) -> Vec<&'static str> {
+    WORDS.iter().cloned().filter(|w| pattern.matches(w)).collect()
+  }
+
+  #[test]
+  fn anchored() {
+    assert_eq!(matching(&Prefix("ab".into())), vec!["ab", "abbot", "about"]);
+    assert_eq!(matching(&Prefix("A-b".into())), vec!["ab", "abbot", "about"]);
+    assert_eq!(matching(&Prefix("ç".into())), vec!["çay", "çağ"]);
+    assert_eq!(matching(&Suffix("ab".into())), vec!["ab", "stab", "tab"]);
+    assert_eq!(matching(&Suffix("é".into())), vec!["aé", "tabbé"]);
+  }
+
+  #[test]
+  fn wildcards() {
+    assert_eq!(
+      matching(&Blanks("_a_".into())),
+      vec!["bat", "tab", "çay", "çağ"]
+    );
+    assert_eq!(matching(&Blanks("__".into())), vec!["ab", "aé", "éa"]);
+    assert_eq!(matching(&Blanks("t_bb_".into())), vec!["tabby", "tabbé"]);
+    assert_eq!(matching(&Blanks("____a_".into())), Vec::<&str>::new());
+  }
+
+  #[test]
+  fn letter_multisets() {
+    assert_eq!(
+      matching(&Contains("bb".into())),
+      vec!["abbot", "tabby", "tabbé"]
+    );
+    assert_eq!(matching(&Contains("aa".into())), vec!["ağaç", "banana"]);
+    assert_eq!(
+      matching(&Contains("ça".into())),
+      vec!["ağaç", "çay", "çağ"]
+    );
+    assert_eq!(
+      matching(&Contains("tba".into())),
+      vec!["abbot", "about", "bat", "stab", "tab", "tabby", "tabbé"]
+    );
+  }
+
+  // Everything the LIKE prefilter lets through is checked again, but anything
+  // it drops is gone for good
+  #[test]
+  fn prefilter_keeps_every_match() {
+    let list = word_list();
+
+    let patterns = vec![
+      Prefix("ab".into()),
+      Prefix("ç".into()),
+      Prefix("É".into()),
+      Suffix("ab".into()),
+      Suffix("é".into()),
+      Blanks("_a_".into()),
+      Blanks("__".into()),
+      Blanks("é_".into()),
+      Blanks("t_bb_".into()),
+      Contains("bb".into()),
+      Contains("aa".into()),
+      Contains("ça".into()),
+      Contains("ÉA".into()),
+      Contains("tba".into()),
+    ];
+
+    for pattern in &patterns {
+      assert_eq!(list.find_words(pattern), matching(pattern));
+    }
+  }
+}
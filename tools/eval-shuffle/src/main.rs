@@ -0,0 +1,265 @@
+extern crate bincode;
+extern crate rand;
+extern crate serde;
+
+#[macro_use]
+extern crate diesel;
+#[macro_use]
+extern crate error_chain;
+#[macro_use]
+extern crate serde_derive;
+
+// These are all shared with the game, which uses more of them than this does
+#[allow(dead_code)]
+mod markov;
+#[allow(dead_code)]
+mod models;
+#[allow(dead_code)]
+mod schema;
+#[allow(dead_code)]
+mod shuffle;
+#[allow(dead_code)]
+mod word_list;
+
+use markov::{FreqTable, Markov};
+use shuffle::Hider;
+use std::{
+  collections::{HashMap, HashSet, VecDeque},
+  env,
+  fs::File,
+  io::{self, prelude::*, BufReader},
+  time::{Duration, Instant},
+};
+use word_list::WordList;
+
+error_chain! {
+  foreign_links {
+    Bincode(bincode::Error);
+    Io(io::Error);
+  }
+
+  errors {
+    InvalidArg(expect: String) {
+      description("invalid arguments"),
+      display("invalid arguments: expected {}", expect),
+    }
+
+    ArgParse(msg: String) {
+      description("argument parsing failed"),
+      display("argument parsing failed: {}", msg),
+    }
+
+    NoWords(len: usize) {
+      description("no words found"),
+      display("no words found of length {}", len),
+    }
+  }
+}
+
+#[derive(Default)]
+struct Results {
+  shuffles: usize,
+  set_hits: usize,  // Shuffles spelling out a word from the set
+  dict_hits: usize, // Shuffles spelling out any longer dictionary word
+  bigrams: HashMap<(char, char), f64>,
+  time: Duration,
+}
+
+impl Results {
+  fn add(&mut self, shuffled: &str, set_hider: &Hider, dict_hider: &Hider) {
+    self.shuffles = self.shuffles + 1;
+
+    if set_hider.hits(shuffled) > 0 {
+      self.set_hits = self.set_hits + 1;
+    }
+
+    if dict_hider.hits(shuffled) > 0 {
+      self.dict_hits = self.dict_hits + 1;
+    }
+
+    let chars: Vec<char> = shuffled.chars().collect();
+
+    for pair in chars.windows(2) {
+      let count = self.bigrams.entry((pair[0], pair[1])).or_insert(0.0);
+      *count = *count + 1.0;
+    }
+  }
+}
+
+// Compares two sets of bigram counts, giving 1 for identical distributions
+// and 0 for ones with nothing in common
+fn cosine_similarity(
+  a: &HashMap<(char, char), f64>,
+  b: &HashMap<(char, char), f64>,
+) -> f64 {
+  let dot = a
+    .iter()
+    .filter_map(|(k, x)| b.get(k).map(|y| x * y))
+    .fold(0.0, |s, p| s + p);
+  let norm = |m: &HashMap<(char, char), f64>| {
+    m.values().fold(0.0, |s, x| s + x * x).sqrt()
+  };
+
+  let denom = norm(a) * norm(b);
+
+  if denom > 0.0 {
+    dot / denom
+  } else {
+    0.0
+  }
+}
+
+fn load_model(path: &str) -> Result<FreqTable<char>> {
+  Ok(markov::load(BufReader::new(File::open(path)?))?)
+}
+
+fn run() -> Result<()> {
+  let mut args: VecDeque<_> = env::args().collect();
+  args.pop_front(); // drop argv[0]
+
+  fn parse_arg<T>(args: &mut VecDeque<String>, expect: &str) -> Result<T>
+  where
+    T: std::str::FromStr,
+    <T as std::str::FromStr>::Err: std::string::ToString,
+  {
+    match args.pop_front() {
+      Some(a) => a,
+      None => return Err(ErrorKind::InvalidArg(expect.into()).into()),
+    }.parse()
+      .map_err(|e: <T as std::str::FromStr>::Err| {
+        ErrorKind::ArgParse(e.to_string()).into()
+      })
+  }
+
+  let mut len: usize = 6;
+  let mut rounds: usize = 100;
+  let mut shuffles: usize = 20;
+  let mut order: usize = 1;
+  let mut seed: u64 = 0;
+  let mut model_file: Option<String> = None;
+
+  while let Some(arg) = args.pop_front() {
+    match arg.as_str() {
+      "--len" => len = parse_arg(&mut args, "a word length")?,
+      "--rounds" => rounds = parse_arg(&mut args, "a number of rounds")?,
+      "--shuffles" => shuffles = parse_arg(&mut args, "a number of shuffles")?,
+      "--order" => order = parse_arg(&mut args, "a context length")?,
+      "--seed" => seed = parse_arg(&mut args, "a number")?,
+      "--model" => model_file = Some(parse_arg(&mut args, "a filename")?),
+      a => {
+        return Err(
+          ErrorKind::ArgParse(format!("unexpected argument '{}'", a)).into(),
+        )
+      }
+    }
+  }
+
+  if order == 0 {
+    return Err(ErrorKind::InvalidArg("an order of at least 1".into()).into());
+  }
+
+  if rounds == 0 || shuffles == 0 {
+    return Err(
+      ErrorKind::InvalidArg("at least 1 round and shuffle".into()).into(),
+    );
+  }
+
+  let words = WordList::new("etc/words.sqlite3");
+
+  let global_model = match model_file {
+    Some(f) => {
+      let mut model = load_model(&f)?;
+      model.retain(|k, _| k.len() <= order);
+      model
+    }
+    None => words.get_transitions(order),
+  };
+
+  let dict: HashSet<String> =
+    words.get_words(shuffle::HIDE_MIN_LEN).into_iter().collect();
+  let no_words: HashSet<String> = HashSet::new();
+
+  // The word list's own bigrams, for comparing the shuffles against
+  let reference: HashMap<(char, char), f64> = global_model
+    .iter()
+    .filter(|(k, _)| k.len() == 1)
+    .flat_map(|(k, tos)| tos.iter().map(move |(to, f)| ((k[0], *to), *f)))
+    .collect();
+
+  let mut rng = shuffle::seeded_rng(seed);
+  let mut results: Vec<Results> = Vec::new();
+
+  println!(
+    "evaluating {} round(s) of {} shuffle(s) with {}-letter words...",
+    rounds, shuffles, len
+  );
+
+  // Rounds are modeled exactly as the game does it, through shuffle.rs
+  println!(
+    "round models: set weight {}, length exponent {}, smoothing {}",
+    shuffle::SET_MODEL_WEIGHT,
+    shuffle::SET_LEN_EXPONENT,
+    shuffle::MODEL_SMOOTHING
+  );
+
+  for _ in 0..rounds {
+    let key = match words.random_set_key(len, &Default::default(), &mut rng) {
+      Some(k) => k,
+      None => return Err(ErrorKind::NoWords(len).into()),
+    };
+    let set = words.get_set(&key);
+
+    let markov = Markov::new(shuffle::round_model(&set, &global_model, order));
+    let strategies =
+      shuffle::strategies(&markov, Hider::new(&set, &dict, &markov));
+
+    let set_hider = Hider::new(&set, &no_words, &markov);
+    let dict_hider = Hider::new(&[], &dict, &markov);
+
+    if results.is_empty() {
+      results = strategies.iter().map(|_| Default::default()).collect();
+    }
+
+    for (strategy, results) in strategies.iter().zip(results.iter_mut()) {
+      for _ in 0..shuffles {
+        let start = Instant::now();
+        let shuffled = strategy.shuffle(&key, &mut rng);
+        results.time = results.time + start.elapsed();
+
+        results.add(&shuffled, &set_hider, &dict_hider);
+      }
+    }
+  }
+
+  println!(
+    "{:>12}  {:>9}  {:>10}  {:>10}  {:>12}",
+    "strategy", "set words", "dict words", "bigram sim", "time/shuffle"
+  );
+
+  for (name, results) in shuffle::names().iter().zip(&results) {
+    let n = results.shuffles as f64;
+    let time = results.time.as_secs() as f64 * 1e6
+      + results.time.subsec_nanos() as f64 / 1e3;
+
+    println!(
+      "{:>12}  {:>8.1}%  {:>9.1}%  {:>10.3}  {:>10.1}us",
+      name,
+      results.set_hits as f64 / n * 100.0,
+      results.dict_hits as f64 / n * 100.0,
+      cosine_similarity(&results.bigrams, &reference),
+      time / n
+    );
+  }
+
+  Ok(())
+}
+
+fn main() {
+  match run() {
+    Ok(_) => return,
+    Err(e) => {
+      writeln!(io::stderr(), "an error occurred: {}", e).unwrap();
+      std::process::exit(1);
+    }
+  }
+}
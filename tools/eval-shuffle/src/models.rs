@@ -0,0 +1 @@
+../../../src/models.rs
\ No newline at end of file
@@ -0,0 +1 @@
+../../../src/schema.rs
\ No newline at end of file
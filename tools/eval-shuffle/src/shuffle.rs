@@ -0,0 +1 @@
+../../../src/shuffle.rs
\ No newline at end of file
@@ -0,0 +1 @@
+../../../src/word_list.rs
\ No newline at end of file
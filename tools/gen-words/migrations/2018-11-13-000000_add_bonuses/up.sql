@@ -0,0 +1,5 @@
+CREATE TABLE bonuses (
+  oid  INTEGER PRIMARY KEY,
+  id   INTEGER NOT NULL,
+  norm TEXT NOT NULL
+) WITHOUT ROWID;
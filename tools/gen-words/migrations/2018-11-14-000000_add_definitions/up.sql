@@ -0,0 +1,7 @@
+CREATE TABLE definitions (
+  oid        INTEGER PRIMARY KEY,
+  norm       TEXT NOT NULL,
+  definition TEXT NOT NULL
+) WITHOUT ROWID;
+
+CREATE INDEX definitions_norm ON definitions (norm);
@@ -0,0 +1,4 @@
+DROP INDEX forms_id;
+DROP INDEX sets_id;
+DROP INDEX bonuses_id;
+DROP INDEX set_keys_len;
@@ -0,0 +1,4 @@
+CREATE INDEX forms_id ON forms (id);
+CREATE INDEX sets_id ON sets (id);
+CREATE INDEX bonuses_id ON bonuses (id);
+CREATE INDEX set_keys_len ON set_keys (len);
@@ -0,0 +1,6 @@
+CREATE TABLE transitions (
+  oid     INTEGER PRIMARY KEY,
+  context TEXT NOT NULL,
+  next    TEXT NOT NULL,
+  weight  DOUBLE NOT NULL
+) WITHOUT ROWID;
@@ -0,0 +1,8 @@
+# Long keys with short words left as bonus words
+min-valid-len = 4
+min-len = 7
+max-len = 12
+max-len-difference = 4
+
+# Obscure words are fair game here
+blacklist-categories = offensive, proper-nouns, abbreviations
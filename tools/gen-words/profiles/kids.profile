@@ -0,0 +1,6 @@
+# Short, easy levels: 3- to 6-letter keys, with every word of 3 or more
+# letters required
+min-valid-len = 3
+min-len = 3
+max-len = 6
+max-len-difference = 3
@@ -1,3 +1,3 @@
 #!/usr/bin/env sh
-diesel migration redo || diesel migration run
+diesel migration run
 cargo run --release $@
\ No newline at end of file
@@ -0,0 +1,428 @@
+use super::*;
+use std::fs::OpenOptions;
+
+// A single line of a blacklist category, matched against normalized words
+pub enum Rule {
+  Exact(Normalized),     // word
+  Substring(Normalized), // ~text, matching any word containing it
+  Pattern(Regex),        // /regex/, matching anywhere unless anchored
+}
+
+impl Rule {
+  fn matches(&self, word: &Normalized) -> bool {
+    match self {
+      Rule::Exact(w) => w == word,
+      Rule::Substring(s) => word.0.contains(&s.0),
+      Rule::Pattern(r) => r.is_match(&word.0),
+    }
+  }
+}
+
+pub struct Category {
+  pub name: String,
+  pub path: PathBuf,
+  pub rules: Vec<(String, Rule)>, // Each rule along with its line as written
+  pub exceptions: HashSet<Normalized>, // !word, never matched by any rule here
+  exact: HashMap<Normalized, usize>, // Indexes the exact rules in rules
+}
+
+// Which category and rule blacklisted a word
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Match<'a> {
+  pub category: &'a str,
+  pub rule: &'a str,
+}
+
+// What the blacklist makes of a word
+#[derive(PartialEq, Eq, Debug)]
+pub enum Verdict<'a> {
+  Clean,
+  Blacklisted(Match<'a>),
+  Whitelisted, // A rule matched, but the whitelist overrode it
+}
+
+// How a decision made in review is written to a category
+#[derive(Clone, Copy)]
+pub enum Entry {
+  Exact,     // word
+  Substring, // ~text
+  Exception, // !word
+}
+
+// Blacklist rules by category, along with the words to let through regardless
+pub struct Blacklist {
+  pub categories: Vec<Category>,
+  pub whitelist: HashSet<Normalized>,
+}
+
+// Reads a list of lines, ignoring blank lines and lines starting with #
+fn read_lines(path: &Path) -> Result<Vec<String>> {
+  lazy_static! {
+    static ref COMMENT_RE: Regex = Regex::new(r"^\s*#").unwrap();
+  }
+
+  let file = BufReader::new(File::open(path)?);
+  let mut ret = Vec::new();
+
+  for line in file.lines() {
+    let line = line?;
+
+    if COMMENT_RE.is_match(&line) || line.trim().is_empty() {
+      continue;
+    }
+
+    ret.push(line.trim().to_string());
+  }
+
+  Ok(ret)
+}
+
+// Finds the categories in a blacklist directory, one per .txt file
+fn find_categories(dir: &Path) -> Result<BTreeMap<String, PathBuf>> {
+  let mut ret = BTreeMap::new();
+
+  for entry in fs::read_dir(dir)? {
+    let path = entry?.path();
+
+    if path.extension().map_or(false, |e| e == "txt") {
+      let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+
+      ret.insert(name, path);
+    }
+  }
+
+  Ok(ret)
+}
+
+// Writes a word as a line of a category file
+pub fn write_entry<W>(out: &mut W, entry: Entry, word: &str) -> Result<()>
+where
+  W: Write,
+{
+  match entry {
+    Entry::Exact => writeln!(out, "{}", word)?,
+    Entry::Substring => writeln!(out, "~{}", word)?,
+    Entry::Exception => writeln!(out, "!{}", word)?,
+  }
+
+  Ok(())
+}
+
+impl Category {
+  fn read(name: String, path: PathBuf, locale: &Locale) -> Result<Self> {
+    let lines = read_lines(&path)?;
+
+    Self::parse(name, path, lines, locale)
+  }
+
+  fn parse(
+    name: String,
+    path: PathBuf,
+    lines: Vec<String>,
+    locale: &Locale,
+  ) -> Result<Self> {
+    let mut rules = Vec::new();
+    let mut exceptions = HashSet::new();
+    let mut exact = HashMap::new();
+
+    for line in lines {
+      let rule = if line.starts_with('!') {
+        exceptions.insert(normalize(&line[1..], locale));
+        continue;
+      } else if line.starts_with('~') {
+        Rule::Substring(normalize(&line[1..], locale))
+      } else if line.len() > 1 && line.starts_with('/') && line.ends_with('/') {
+        match Regex::new(&line[1..line.len() - 1]) {
+          Ok(r) => Rule::Pattern(r),
+          Err(e) => {
+            return Err(
+              ErrorKind::InvalidProfile(format!(
+                "bad rule '{}' in {}: {}",
+                line,
+                path.display(),
+                e
+              )).into(),
+            )
+          }
+        }
+      } else {
+        Rule::Exact(normalize(&line, locale))
+      };
+
+      match rule {
+        // A rule that normalizes away entirely would match everything
+        Rule::Exact(ref w) | Rule::Substring(ref w) if w.0.is_empty() => {
+          continue
+        }
+        Rule::Exact(ref w) => {
+          exact.entry(w.clone()).or_insert(rules.len());
+        }
+        _ => {}
+      }
+
+      rules.push((line, rule));
+    }
+
+    Ok(Self {
+      name,
+      path,
+      rules,
+      exceptions,
+      exact,
+    })
+  }
+
+  fn check(&self, word: &Normalized) -> Option<&str> {
+    if self.exceptions.contains(word) {
+      return None;
+    }
+
+    if let Some(&i) = self.exact.get(word) {
+      return Some(&self.rules[i].0);
+    }
+
+    self
+      .rules
+      .iter()
+      .find(|(_, r)| match r {
+        Rule::Exact(_) => false,
+        r => r.matches(word),
+      })
+      .map(|(l, _)| l.as_str())
+  }
+
+  // Opens this category's file for adding decisions made in review, making
+  // sure they start on a line of their own
+  pub fn append(&self) -> Result<File> {
+    let mut contents = Vec::new();
+    File::open(&self.path)?.read_to_end(&mut contents)?;
+
+    let mut file = OpenOptions::new().append(true).open(&self.path)?;
+
+    if !contents.is_empty() && !contents.ends_with(b"\n") {
+      writeln!(file)?;
+    }
+
+    Ok(file)
+  }
+}
+
+impl Blacklist {
+  // Reads the blacklist named by the profile, which is either a single file or
+  // a directory of categories to pick from
+  pub fn new(profile: &Profile) -> Result<Self> {
+    let locale = profile.locale();
+    let path = Path::new(&profile.blacklist);
+
+    let files = if path.is_dir() {
+      let mut found = find_categories(path)?;
+
+      if profile.blacklist_categories.is_empty() {
+        found
+      } else {
+        let mut files = BTreeMap::new();
+
+        for name in &profile.blacklist_categories {
+          match found.remove(name) {
+            Some(f) => files.insert(name.clone(), f),
+            None => {
+              return Err(
+                ErrorKind::InvalidProfile(format!(
+                  "no blacklist category '{}' in {}",
+                  name, profile.blacklist
+                )).into(),
+              )
+            }
+          };
+        }
+
+        files
+      }
+    } else {
+      if !profile.blacklist_categories.is_empty() {
+        return Err(
+          ErrorKind::InvalidProfile(
+            "blacklist-categories needs a blacklist directory".into(),
+          ).into(),
+        );
+      }
+
+      let name = path
+        .file_stem()
+        .map_or("blacklist".into(), |s| s.to_string_lossy().into_owned());
+
+      let mut files = BTreeMap::new();
+      files.insert(name, path.to_path_buf());
+      files
+    };
+
+    let whitelist: HashSet<Normalized> = match profile.whitelist {
+      Some(ref f) => read_lines(Path::new(f))?
+        .iter()
+        .map(|w| normalize(w, &locale))
+        .collect(),
+      None => HashSet::new(),
+    };
+
+    let mut categories = Vec::new();
+
+    for (name, file) in files {
+      categories.push(Category::read(name, file, &locale)?);
+    }
+
+    Ok(Self {
+      categories,
+      whitelist,
+    })
+  }
+
+  // Finds the first rule blacklisting a word, ignoring the whitelist
+  pub fn check<'a>(&'a self, word: &Normalized) -> Option<Match<'a>> {
+    self.categories.iter().find_map(|c| {
+      c.check(word).map(|rule| Match {
+        category: &c.name,
+        rule,
+      })
+    })
+  }
+
+  // Checks a word against every category, letting the whitelist override them
+  pub fn verdict<'a>(&'a self, word: &Normalized) -> Verdict<'a> {
+    match self.check(word) {
+      Some(_) if self.whitelist.contains(word) => Verdict::Whitelisted,
+      Some(m) => Verdict::Blacklisted(m),
+      None => Verdict::Clean,
+    }
+  }
+
+  // Finds an exactly-blacklisted word contained in a word that got through,
+  // which suggests it might need blacklisting too
+  pub fn suspect(&self, word: &Normalized) -> Option<(&Category, &Normalized)> {
+    if self.whitelist.contains(word) {
+      return None;
+    }
+
+    for category in &self.categories {
+      if category.exceptions.contains(word) {
+        continue;
+      }
+
+      for (_, rule) in &category.rules {
+        if let Rule::Exact(w) = rule {
+          if w != word && word.0.contains(&w.0) {
+            return Some((category, w));
+          }
+        }
+      }
+    }
+
+    None
+  }
+
+  pub fn len(&self) -> usize {
+    self.categories.iter().map(|c| c.rules.len()).sum()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn category(name: &str, lines: &[&str]) -> Category {
+    Category::parse(
+      name.to_string(),
+      PathBuf::from(name),
+      lines.iter().map(|l| l.to_string()).collect(),
+      &Locale::new(None),
+    ).unwrap()
+  }
+
+  fn norm(word: &str) -> Normalized {
+    normalize(word, &Locale::new(None))
+  }
+
+  #[test]
+  fn writes_entries() {
+    let mut out = Vec::new();
+
+    write_entry(&mut out, Entry::Exact, "abbot").unwrap();
+    write_entry(&mut out, Entry::Substring, "bot").unwrap();
+    write_entry(&mut out, Entry::Exception, "robot").unwrap();
+
+    assert_eq!(String::from_utf8(out).unwrap(), "abbot\n~bot\n!robot\n");
+  }
+
+  #[test]
+  fn written_entries_read_back() {
+    let mut out = Vec::new();
+
+    write_entry(&mut out, Entry::Exact, "abbot").unwrap();
+    write_entry(&mut out, Entry::Substring, "bot").unwrap();
+    write_entry(&mut out, Entry::Exception, "robot").unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    let c = category("test", &lines);
+
+    assert_eq!(c.check(&norm("abbot")), Some("abbot"));
+    assert_eq!(c.check(&norm("bots")), Some("~bot"));
+    assert_eq!(c.check(&norm("robot")), None);
+  }
+
+  #[test]
+  fn exceptions_beat_rules() {
+    let c = category(
+      "test",
+      &["cat", "~dog", "/^bird/", "!cat", "!hotdog", "!birdie"],
+    );
+
+    assert_eq!(c.check(&norm("cat")), None);
+    assert_eq!(c.check(&norm("hotdog")), None);
+    assert_eq!(c.check(&norm("birdie")), None);
+
+    assert_eq!(c.check(&norm("dogs")), Some("~dog"));
+    assert_eq!(c.check(&norm("birds")), Some("/^bird/"));
+    assert_eq!(c.check(&norm("Hot-Dogs")), Some("~dog"));
+  }
+
+  #[test]
+  fn exceptions_only_apply_to_their_category() {
+    let blacklist = Blacklist {
+      categories: vec![
+        category("a", &["~dog", "!hotdog"]),
+        category("b", &["hotdog"]),
+      ],
+      whitelist: HashSet::new(),
+    };
+
+    assert_eq!(
+      blacklist.verdict(&norm("hotdog")),
+      Verdict::Blacklisted(Match {
+        category: "b",
+        rule: "hotdog",
+      })
+    );
+  }
+
+  #[test]
+  fn whitelist_beats_everything() {
+    let blacklist = Blacklist {
+      categories: vec![
+        category("a", &["cat", "~dog", "/^bird/"]),
+        category("b", &["cat"]),
+      ],
+      whitelist: ["cat", "hotdog", "birdie"].iter().map(|w| norm(w)).collect(),
+    };
+
+    for word in &["cat", "hotdog", "birdie"] {
+      assert_eq!(blacklist.verdict(&norm(word)), Verdict::Whitelisted);
+      assert!(blacklist.suspect(&norm(word)).is_none());
+    }
+
+    assert_eq!(blacklist.verdict(&norm("fish")), Verdict::Clean);
+    assert!(match blacklist.verdict(&norm("dogs")) {
+      Verdict::Blacklisted(_) => true,
+      _ => false,
+    });
+  }
+}
@@ -0,0 +1,193 @@
+use super::*;
+use std::fmt;
+
+// Why a key was dropped, in the order they're checked
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Reject {
+  TooFew,
+  TooMany,
+  TooFewOfLen(usize),
+  NoCommon,
+}
+
+impl fmt::Display for Reject {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Reject::TooFew => write!(f, "too few words"),
+      Reject::TooMany => write!(f, "too many words"),
+      Reject::TooFewOfLen(len) => write!(f, "too few {}-letter words", len),
+      Reject::NoCommon => write!(f, "no common full-length word"),
+    }
+  }
+}
+
+// Decides whether a key's set makes a good enough level to keep
+pub struct Constraints {
+  min_set_size: usize,
+  max_set_size: usize,
+  min_per_len: usize,
+  common: Option<HashSet<Normalized>>,
+}
+
+impl Constraints {
+  pub fn new(profile: &Profile) -> Result<Self> {
+    let common = match profile.common_words {
+      Some(ref f) => {
+        let file = BufReader::new(File::open(f)?);
+        let locale = profile.locale();
+        let mut common = HashSet::new();
+
+        for line in file.lines() {
+          common.insert(normalize(line?.trim(), &locale));
+        }
+
+        println!("read {} common word(s)", common.len());
+
+        Some(common)
+      }
+      None => None,
+    };
+
+    Ok(Self {
+      min_set_size: profile.min_set_size,
+      max_set_size: profile.max_set_size,
+      min_per_len: profile.min_per_len,
+      common,
+    })
+  }
+
+  // Checks the words required to clear a key's level, which are sorted by
+  // length as build_set leaves them
+  pub fn check(&self, key: &Depermuted, list: &[Normalized]) -> Option<Reject> {
+    if list.len() < self.min_set_size {
+      return Some(Reject::TooFew);
+    }
+
+    if self.max_set_size > 0 && list.len() > self.max_set_size {
+      return Some(Reject::TooMany);
+    }
+
+    if self.min_per_len > 0 {
+      if let Some(shortest) = list.first() {
+        for len in shortest.len()..key.len() {
+          let count = list.iter().filter(|n| n.len() == len).count();
+
+          if count < self.min_per_len {
+            return Some(Reject::TooFewOfLen(len));
+          }
+        }
+      }
+    }
+
+    if let Some(ref common) = self.common {
+      if !list
+        .iter()
+        .any(|n| n.len() == key.len() && common.contains(n))
+      {
+        return Some(Reject::NoCommon);
+      }
+    }
+
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn constraints() -> Constraints {
+    Constraints {
+      min_set_size: 0,
+      max_set_size: 0,
+      min_per_len: 0,
+      common: None,
+    }
+  }
+
+  // The key "beast" and its words, sorted by length
+  fn check(c: &Constraints, words: &[&str]) -> Option<Reject> {
+    let list: Vec<Normalized> =
+      words.iter().map(|w| Normalized(w.to_string())).collect();
+
+    c.check(&subset::depermute("beast"), &list)
+  }
+
+  static WORDS: [&str; 6] = ["bat", "set", "tab", "bast", "beat", "beast"];
+
+  #[test]
+  fn accepts() {
+    assert_eq!(check(&constraints(), &WORDS), None);
+  }
+
+  #[test]
+  fn too_few() {
+    let c = Constraints {
+      min_set_size: 7,
+      ..constraints()
+    };
+
+    assert_eq!(check(&c, &WORDS), Some(Reject::TooFew));
+    assert_eq!(check(&c, &[]), Some(Reject::TooFew));
+  }
+
+  #[test]
+  fn too_many() {
+    let c = Constraints {
+      max_set_size: 5,
+      ..constraints()
+    };
+
+    assert_eq!(check(&c, &WORDS), Some(Reject::TooMany));
+    assert_eq!(check(&c, &WORDS[1..]), None);
+  }
+
+  #[test]
+  fn too_few_of_len() {
+    let c = Constraints {
+      min_per_len: 2,
+      ..constraints()
+    };
+
+    assert_eq!(check(&c, &WORDS), None);
+    assert_eq!(
+      check(&c, &["bat", "set", "bast", "beast"]),
+      Some(Reject::TooFewOfLen(4))
+    );
+    assert_eq!(
+      check(&c, &["bat", "bast", "beat", "beast"]),
+      Some(Reject::TooFewOfLen(3))
+    );
+
+    // Lengths shorter than the shortest word aren't counted
+    assert_eq!(check(&c, &["bast", "beat", "beast"]), None);
+  }
+
+  #[test]
+  fn no_common() {
+    let c = Constraints {
+      common: Some(
+        ["bat", "beast"]
+          .iter()
+          .map(|w| Normalized(w.to_string()))
+          .collect(),
+      ),
+      ..constraints()
+    };
+
+    assert_eq!(check(&c, &WORDS), None);
+    assert_eq!(check(&c, &WORDS[..5]), Some(Reject::NoCommon));
+  }
+
+  #[test]
+  fn checked_in_order() {
+    let c = Constraints {
+      min_set_size: 10,
+      max_set_size: 2,
+      min_per_len: 5,
+      common: Some(HashSet::new()),
+    };
+
+    assert_eq!(check(&c, &WORDS), Some(Reject::TooFew));
+  }
+}
@@ -0,0 +1,514 @@
+use super::*;
+use encoding_rs::{Encoding, UTF_8};
+
+// Flags are stored as numbers, whichever way the dictionary writes them
+type Flag = u64;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FlagType {
+  Short, // One character each
+  Long,  // Two characters each
+  Num,   // Comma-separated numbers
+}
+
+struct Rule {
+  strip: String,
+  add: String,
+  flags: Vec<Flag>, // Continuation flags, allowing another affix on top
+  cond: Regex,
+}
+
+struct Affix {
+  cross: bool, // Whether it can be combined with affixes of the other kind
+  rules: Vec<Rule>,
+}
+
+// The parts of an .aff file needed to expand a dictionary into full words
+pub struct Aff {
+  encoding: &'static Encoding,
+  flag_type: FlagType,
+  aliases: Option<Vec<Vec<Flag>>>, // With AF, the sets of flags numbered from 1
+  prefixes: HashMap<Flag, Affix>,
+  suffixes: HashMap<Flag, Affix>,
+  need_affix: Option<Flag>,
+  forbidden: Option<Flag>,
+  only_in_compound: Option<Flag>,
+  ignore: Vec<char>,
+  pub lang: Option<String>,
+}
+
+// Reads a file in whatever encoding its SET line names (or UTF-8), returning
+// its lines and the encoding used
+fn read_lines(
+  path: &Path,
+  encoding: Option<&'static Encoding>,
+) -> Result<(Vec<String>, &'static Encoding)> {
+  let mut bytes = Vec::new();
+  File::open(path)?.read_to_end(&mut bytes)?;
+
+  let encoding = match encoding {
+    Some(e) => e,
+    None => {
+      let text = String::from_utf8_lossy(&bytes);
+
+      match text
+        .lines()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>())
+        .find(|p| p.len() > 1 && p[0] == "SET")
+      {
+        Some(parts) => {
+          let label = parts[1].trim_start_matches("microsoft-");
+
+          match Encoding::for_label(label.as_bytes()) {
+            Some(e) => e,
+            None => {
+              return Err(
+                ErrorKind::Hunspell(format!("unknown encoding '{}'", label))
+                  .into(),
+              )
+            }
+          }
+        }
+        None => UTF_8,
+      }
+    }
+  };
+
+  let (text, _, _) = encoding.decode(&bytes);
+
+  Ok((text.lines().map(|l| l.to_string()).collect(), encoding))
+}
+
+// Turns an affix condition into a regex matching at the start or end of a
+// word.  Conditions only use literal characters, . and [...] groups.
+fn condition(cond: &str, suffix: bool) -> Result<Regex> {
+  let mut re = String::new();
+  let mut in_group = false;
+
+  for c in cond.chars() {
+    match c {
+      '[' if !in_group => {
+        in_group = true;
+        re.push('[');
+      }
+      ']' if in_group => {
+        in_group = false;
+        re.push(']');
+      }
+      '^' if in_group && re.ends_with('[') => re.push('^'),
+      '.' if !in_group => re.push('.'),
+      c => re.push_str(&regex::escape(&c.to_string())),
+    }
+  }
+
+  let re = if suffix {
+    format!("(?:{})$", re)
+  } else {
+    format!("^(?:{})", re)
+  };
+
+  Regex::new(&re).map_err(|e| {
+    ErrorKind::Hunspell(format!("bad condition '{}': {}", cond, e)).into()
+  })
+}
+
+impl Aff {
+  pub fn read(path: &Path) -> Result<Self> {
+    let (lines, encoding) = read_lines(path, None)?;
+
+    let mut ret = Self {
+      encoding,
+      flag_type: FlagType::Short,
+      aliases: None,
+      prefixes: HashMap::new(),
+      suffixes: HashMap::new(),
+      need_affix: None,
+      forbidden: None,
+      only_in_compound: None,
+      ignore: Vec::new(),
+      lang: None,
+    };
+
+    // The flag type has to be known before any flags can be read, and the
+    // ignored characters before any affixes
+    for line in &lines {
+      let parts: Vec<&str> = line.split_whitespace().collect();
+
+      if parts.len() < 2 {
+        continue;
+      }
+
+      match parts[0] {
+        "FLAG" => {
+          ret.flag_type = match parts[1] {
+            "long" => FlagType::Long,
+            "num" => FlagType::Num,
+            _ => FlagType::Short, // UTF-8 flags are just chars once decoded
+          }
+        }
+        "IGNORE" => ret.ignore = parts[1].chars().collect(),
+        _ => {}
+      }
+    }
+
+    // The first AF line gives the number of aliases, and each one after it is
+    // a set of flags.  These have to be known before any continuation flags.
+    for line in &lines {
+      let parts: Vec<&str> = line.split_whitespace().collect();
+
+      if parts.len() < 2 || parts[0] != "AF" {
+        continue;
+      }
+
+      if ret.aliases.is_none() {
+        ret.aliases = Some(Vec::new());
+        continue;
+      }
+
+      let flags = ret.parse_flags(parts[1]);
+      ret.aliases.as_mut().unwrap().push(flags);
+    }
+
+    for line in &lines {
+      let parts: Vec<&str> = line.split_whitespace().collect();
+
+      if parts.len() < 2 || parts[0].starts_with('#') {
+        continue;
+      }
+
+      match parts[0] {
+        "LANG" => ret.lang = Some(parts[1].to_string()),
+        "NEEDAFFIX" | "PSEUDOROOT" => ret.need_affix = ret.flag(parts[1]),
+        "FORBIDDENWORD" => ret.forbidden = ret.flag(parts[1]),
+        "ONLYINCOMPOUND" => ret.only_in_compound = ret.flag(parts[1]),
+        kind @ "PFX" | kind @ "SFX" => {
+          let suffix = kind == "SFX";
+          let flag = match ret.flag(parts[1]) {
+            Some(f) => f,
+            None => continue,
+          };
+
+          let affixes = if suffix {
+            &mut ret.suffixes
+          } else {
+            &mut ret.prefixes
+          };
+
+          // The first line for each flag is a header giving the rule count
+          if !affixes.contains_key(&flag) {
+            affixes.insert(
+              flag,
+              Affix {
+                cross: parts.get(2) == Some(&"Y"),
+                rules: Vec::new(),
+              },
+            );
+            continue;
+          }
+
+          if parts.len() < 4 {
+            return Err(
+              ErrorKind::Hunspell(format!("bad affix rule '{}'", line)).into(),
+            );
+          }
+
+          let unzero = |s: &str| if s == "0" { "" } else { s }.to_string();
+
+          let mut add = parts[3].splitn(2, '/');
+          let add_str = unzero(add.next().unwrap());
+          let add_flags = add.next().unwrap_or("");
+
+          // Ignored characters are dropped from affixes just like stems
+          let rule = Rule {
+            strip: ret.strip_ignored(&unzero(parts[2])),
+            add: ret.strip_ignored(&add_str),
+            flags: ret.parse_flag_field(add_flags),
+            cond: condition(parts.get(4).cloned().unwrap_or("."), suffix)?,
+          };
+
+          let affixes = if suffix {
+            &mut ret.suffixes
+          } else {
+            &mut ret.prefixes
+          };
+
+          affixes.get_mut(&flag).unwrap().rules.push(rule);
+        }
+        _ => {}
+      }
+    }
+
+    Ok(ret)
+  }
+
+  fn flag(&self, s: &str) -> Option<Flag> {
+    self.parse_flags(s).into_iter().next()
+  }
+
+  // Reads the flags of a .dic entry or an affix's continuation flags, which
+  // with AF are the number of one of the listed sets of flags instead
+  fn parse_flag_field(&self, s: &str) -> Vec<Flag> {
+    match self.aliases {
+      Some(ref aliases) if !s.is_empty() => match s.parse::<usize>() {
+        Ok(i) if i > 0 && i <= aliases.len() => aliases[i - 1].clone(),
+        _ => Vec::new(),
+      },
+      _ => self.parse_flags(s),
+    }
+  }
+
+  fn parse_flags(&self, s: &str) -> Vec<Flag> {
+    if s.is_empty() {
+      return Vec::new();
+    }
+
+    match self.flag_type {
+      FlagType::Short => s.chars().map(|c| c as Flag).collect(),
+      FlagType::Long => {
+        let chars: Vec<char> = s.chars().collect();
+
+        chars
+          .chunks(2)
+          .map(|p| p.iter().fold(0, |f, c| (f << 32) | *c as Flag))
+          .collect()
+      }
+      FlagType::Num => {
+        s.split(',').filter_map(|n| n.trim().parse().ok()).collect()
+      }
+    }
+  }
+
+  fn strip_ignored(&self, s: &str) -> String {
+    s.chars().filter(|c| !self.ignore.contains(c)).collect()
+  }
+
+  fn apply(rule: &Rule, word: &str, suffix: bool) -> Option<String> {
+    if suffix {
+      if word.ends_with(&rule.strip) && rule.cond.is_match(word) {
+        let stem = &word[..word.len() - rule.strip.len()];
+
+        return Some(format!("{}{}", stem, rule.add));
+      }
+    } else if word.starts_with(&rule.strip) && rule.cond.is_match(word) {
+      return Some(format!("{}{}", rule.add, &word[rule.strip.len()..]));
+    }
+
+    None
+  }
+
+  // Expands a stem and its flags into every full form it stands for
+  fn expand(&self, stem: &str, flags: &[Flag], out: &mut Vec<String>) {
+    let has = |flags: &[Flag], f: Option<Flag>| {
+      f.map_or(false, |f| flags.contains(&f))
+    };
+
+    if has(flags, self.forbidden) || has(flags, self.only_in_compound) {
+      return;
+    }
+
+    if !has(flags, self.need_affix) {
+      out.push(stem.to_string());
+    }
+
+    let prefixed = |word: &str, flags: &[Flag], out: &mut Vec<String>| {
+      for flag in flags {
+        if let Some(affix) = self.prefixes.get(flag) {
+          if !affix.cross {
+            continue;
+          }
+
+          for rule in &affix.rules {
+            if let Some(w) = Self::apply(rule, word, false) {
+              out.push(w);
+            }
+          }
+        }
+      }
+    };
+
+    for flag in flags {
+      if let Some(affix) = self.suffixes.get(flag) {
+        for rule in &affix.rules {
+          let word = match Self::apply(rule, stem, true) {
+            Some(w) => w,
+            None => continue,
+          };
+
+          if !has(&rule.flags, self.need_affix) {
+            out.push(word.clone());
+          }
+
+          // A second suffix can go on top of the first, and the stem's
+          // prefixes on top of both
+          for flag2 in &rule.flags {
+            if let Some(affix2) = self.suffixes.get(flag2) {
+              for rule2 in &affix2.rules {
+                if let Some(w) = Self::apply(rule2, &word, true) {
+                  if affix.cross && affix2.cross {
+                    prefixed(&w, flags, out);
+                  }
+
+                  out.push(w);
+                }
+              }
+            }
+          }
+
+          if affix.cross {
+            prefixed(&word, flags, out);
+            prefixed(&word, &rule.flags, out);
+          }
+        }
+      }
+
+      if let Some(affix) = self.prefixes.get(flag) {
+        for rule in &affix.rules {
+          let word = match Self::apply(rule, stem, false) {
+            Some(w) => w,
+            None => continue,
+          };
+
+          if !has(&rule.flags, self.need_affix) {
+            out.push(word.clone());
+          }
+
+          if !affix.cross {
+            continue;
+          }
+
+          // A suffix can go on top of the prefix, but not another prefix
+          for flag2 in &rule.flags {
+            if let Some(affix2) = self.suffixes.get(flag2) {
+              if !affix2.cross {
+                continue;
+              }
+
+              for rule2 in &affix2.rules {
+                if let Some(w) = Self::apply(rule2, &word, true) {
+                  out.push(w);
+                }
+              }
+            }
+          }
+        }
+      }
+    }
+  }
+}
+
+// Splits a .dic line into its word and flags, allowing for escaped slashes and
+// ignoring any morphological fields after it
+fn split_entry(line: &str) -> (String, &str) {
+  let entry = line.split(|c| c == '\t' || c == ' ').next().unwrap_or("");
+  let mut word = String::new();
+  let mut chars = entry.char_indices();
+
+  while let Some((i, c)) = chars.next() {
+    match c {
+      '\\' => {
+        if let Some((_, c)) = chars.next() {
+          word.push(c);
+        }
+      }
+      '/' => return (word, &entry[i + 1..]),
+      c => word.push(c),
+    }
+  }
+
+  (word, "")
+}
+
+pub struct Dictionary {
+  pub words: Vec<String>,
+  pub lang: Option<String>,
+}
+
+// Reads a .dic file and the .aff file next to it, expanding every entry into
+// all of its forms
+pub fn read(dic: &str) -> Result<Dictionary> {
+  let dic = Path::new(dic);
+  let aff = Aff::read(&dic.with_extension("aff"))?;
+
+  let (lines, _) = read_lines(dic, Some(aff.encoding))?;
+
+  let mut words = BTreeSet::new();
+  let mut forms = Vec::new();
+
+  // The first line is just a count of the entries
+  for line in lines.iter().skip(1) {
+    let (word, flags) = split_entry(line);
+    let word = aff.strip_ignored(&word);
+
+    if word.is_empty() {
+      continue;
+    }
+
+    forms.clear();
+    aff.expand(&word, &aff.parse_flag_field(flags), &mut forms);
+
+    words.extend(forms.drain(..));
+  }
+
+  println!(
+    "expanded {} dictionary entries into {} word(s)",
+    lines.len().saturating_sub(1),
+    words.len()
+  );
+
+  Ok(Dictionary {
+    words: words.into_iter().collect(),
+    lang: aff.lang,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn fixture(name: &str) -> Dictionary {
+    let dic = format!(
+      "{}/tests/fixtures/hunspell/{}.dic",
+      env!("CARGO_MANIFEST_DIR"),
+      name
+    );
+
+    read(&dic).unwrap()
+  }
+
+  #[test]
+  fn expands_fixture() {
+    let dict = fixture("test");
+
+    assert_eq!(dict.lang, Some("en_US".to_string()));
+    assert_eq!(
+      dict.words,
+      vec![
+        "agree",
+        "agreement",
+        "agreements",
+        "coop",
+        "op",
+        "play",
+        "replay",
+        "replays",
+        "send",
+        "sent",
+        "unagree",
+        "unagreement",
+        "unagreements",
+      ]
+    );
+  }
+
+  #[test]
+  fn reads_numbered_flags_with_aliases() {
+    let dict = fixture("aliases");
+
+    assert_eq!(
+      dict.words,
+      vec![
+        "do", "dos", "read", "reread", "work", "worker", "workers", "works",
+      ]
+    );
+  }
+}
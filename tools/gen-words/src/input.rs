@@ -0,0 +1,75 @@
+use super::*;
+
+// The kinds of source a wordlist can be read from
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+  Plain,       // One word per line
+  TwelveDicts, // A directory containing 12dicts and alt12dicts
+  Hunspell,    // A .dic file, with its .aff file alongside
+}
+
+impl Format {
+  pub fn parse(s: &str) -> Option<Self> {
+    match s {
+      "plain" => Some(Format::Plain),
+      "12dicts" => Some(Format::TwelveDicts),
+      "hunspell" => Some(Format::Hunspell),
+      _ => None,
+    }
+  }
+}
+
+// Reads a plain wordlist, skipping blank lines
+pub fn read_plain(file: &str) -> Result<Vec<String>> {
+  let file = BufReader::new(File::open(file)?);
+  let mut words = Vec::new();
+
+  for line in file.lines() {
+    let word = line?.trim().to_string();
+
+    if !word.is_empty() {
+      words.push(word);
+    }
+  }
+
+  Ok(words)
+}
+
+// Reads every word from a source, noting anything the source had to say about
+// them in the report
+pub fn read(
+  path: &str,
+  format: Format,
+  report: &mut Report,
+) -> Result<Vec<String>> {
+  match format {
+    Format::Plain => read_plain(path),
+    Format::TwelveDicts => {
+      let import = twelve_dicts::import(path)?;
+
+      for word in &import.unrejected {
+        println!("UNREJECTED {}", word);
+      }
+
+      for (kind, word) in import.diagnostics {
+        report
+          .input
+          .diagnostics
+          .entry(kind)
+          .or_insert_with(Vec::new)
+          .push(word);
+      }
+
+      report.input.unrejected = import.unrejected;
+
+      Ok(import.words)
+    }
+    Format::Hunspell => {
+      let dict = hunspell::read(path)?;
+
+      report.input.lang = dict.lang;
+
+      Ok(dict.words)
+    }
+  }
+}
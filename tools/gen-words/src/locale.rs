@@ -0,0 +1,90 @@
+use super::*;
+use unicode_normalization::UnicodeNormalization;
+
+// The language rules used when normalizing words.  Only casing actually
+// differs between languages, and only for a few of them.
+#[derive(Clone, Debug, Default)]
+pub struct Locale {
+  turkic: bool, // Dotted and dotless i are separate letters
+}
+
+impl Locale {
+  // Takes a tag like tr, tr_TR or tr-TR
+  pub fn new(tag: Option<&str>) -> Self {
+    let lang = tag.map(|t| {
+      t.split(|c| c == '_' || c == '-')
+        .next()
+        .unwrap_or("")
+        .to_lowercase()
+    });
+
+    Self {
+      turkic: lang.map_or(false, |l| l == "tr" || l == "az"),
+    }
+  }
+
+  // Composes any decomposed characters, so each letter is one char
+  pub fn compose(&self, word: &str) -> String {
+    word.nfc().collect()
+  }
+
+  pub fn to_lowercase(&self, word: &str) -> String {
+    if self.turkic {
+      word
+        .chars()
+        .map(|c| match c {
+          'I' => "ı".to_string(),
+          'İ' => "i".to_string(),
+          c => c.to_lowercase().collect(),
+        })
+        .collect()
+    } else {
+      word.to_lowercase()
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn turkic_i() {
+    for tag in &["tr", "tr_TR", "tr-TR", "az"] {
+      let locale = Locale::new(Some(tag));
+
+      assert_eq!(locale.to_lowercase("I"), "ı");
+      assert_eq!(locale.to_lowercase("İ"), "i");
+      assert_eq!(locale.to_lowercase("IĞDIR"), "ığdır");
+      assert_eq!(locale.to_lowercase("İSTANBUL"), "istanbul");
+    }
+  }
+
+  #[test]
+  fn other_i() {
+    for tag in &[None, Some("en"), Some("en_US"), Some("tra")] {
+      let locale = Locale::new(*tag);
+
+      assert_eq!(locale.to_lowercase("I"), "i");
+      assert_eq!(locale.to_lowercase("IĞDIR"), "iğdir");
+    }
+  }
+
+  #[test]
+  fn composes() {
+    let locale = Locale::default();
+    let decomposed = "cafe\u{301}";
+
+    assert_eq!(decomposed.chars().count(), 5);
+    assert_eq!(locale.compose(decomposed), "café");
+    assert_eq!(locale.compose(decomposed).chars().count(), 4);
+
+    // Composing before lowercasing keeps a decomposed dotted I to one letter
+    let tr = Locale::new(Some("tr"));
+    let dotted = "I\u{307}STANBUL";
+
+    assert_eq!(tr.to_lowercase(&tr.compose(dotted)), "istanbul");
+    assert_eq!(normalize(dotted, &tr).0, "istanbul");
+    assert_eq!(normalize("Cafe\u{301}!", &locale).0, "café");
+  }
+}
@@ -1,5 +1,9 @@
 extern crate dotenv;
+extern crate encoding_rs;
 extern crate regex;
+extern crate serde;
+extern crate serde_json;
+extern crate unicode_normalization;
 
 #[macro_use]
 extern crate diesel;
@@ -7,17 +11,40 @@ extern crate diesel;
 extern crate error_chain;
 #[macro_use]
 extern crate lazy_static;
+#[macro_use]
+extern crate serde_derive;
 
+mod blacklist;
+mod constraints;
+mod hunspell;
+mod input;
+mod locale;
 mod models;
+mod profile;
+mod report;
+mod review;
 mod schema;
+mod subset;
 mod thread_pool;
-
+mod twelve_dicts;
+mod update;
+mod verify;
+
+use blacklist::{Blacklist, Verdict};
+use constraints::{Constraints, Reject};
+use input::Format;
+use locale::Locale;
+use profile::Profile;
 use regex::Regex;
+use report::Report;
+use subset::SubsetIndex;
 use std::{
+  cmp,
   collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
   env,
-  fs::File,
-  io::{self, prelude::*, BufReader},
+  fs::{self, File},
+  io::{self, prelude::*, BufReader, BufWriter},
+  path::{Path, PathBuf},
   str,
   sync::{
     atomic::{AtomicUsize, Ordering},
@@ -34,6 +61,7 @@ error_chain! {
     DieselConnection(diesel::ConnectionError);
     EnvVar(std::env::VarError);
     Io(io::Error);
+    Json(serde_json::Error);
   }
 
   errors {
@@ -46,6 +74,39 @@ error_chain! {
       description("argument parsing failed"),
       display("argument parsing failed: {}", msg),
     }
+
+    InvalidProfile(msg: String) {
+      description("invalid generation profile"),
+      display("invalid generation profile: {}", msg),
+    }
+
+    Hunspell(msg: String) {
+      description("invalid Hunspell dictionary"),
+      display("invalid Hunspell dictionary: {}", msg),
+    }
+
+    DatabaseUrl(url: String) {
+      description("DATABASE_URL isn't a file path"),
+      display(
+        "DATABASE_URL must be a plain file path to build into, not '{}'",
+        url
+      ),
+    }
+
+    MissingList(path: String) {
+      description("12dicts list not found"),
+      display("12dicts list not found: {}", path),
+    }
+
+    Bench {
+      description("benchmark results differ"),
+      display("benchmark failed: the indexed and naive searches disagree"),
+    }
+
+    Verify(count: usize) {
+      description("database verification failed"),
+      display("database verification failed: {} problem(s) found", count),
+    }
   }
 }
 
@@ -55,129 +116,157 @@ struct WordlistForm {
   full: String,
 }
 
-type CharCounts = HashMap<char, usize>;
+static MIN_BONUS_LEN: usize = 2;
+static MODEL_ORDER: usize = 3; // Longest context kept in the letter model
 
-fn count_chars(s: &str) -> CharCounts {
-  let mut ret = CharCounts::new();
-
-  for c in s.chars() {
-    use std::collections::hash_map::Entry::*;
+#[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
+struct Normalized(String); // Used as a string with nonword characters stripped
+#[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
+struct Depermuted(String); // Used as a Normalized with its characters sorted
 
-    match ret.entry(c) {
-      Occupied(o) => {
-        let val = o.into_mut();
-        *val = *val + 1;
-      }
-      Vacant(v) => {
-        v.insert(1);
-      }
-    }
+// Lengths are in letters rather than bytes, so words outside ASCII aren't
+// counted as longer than they are
+impl Normalized {
+  fn len(&self) -> usize {
+    self.0.chars().count()
   }
+}
 
-  ret
+impl Depermuted {
+  fn len(&self) -> usize {
+    self.0.chars().count()
+  }
 }
 
-fn is_subseq(count: &CharCounts, of: &CharCounts) -> bool {
-  count.iter().all(|(c, n)| n <= of.get(c).unwrap_or(&0))
+lazy_static! {
+  // Words containing these are never used
+  static ref REJECT_RE: Regex = Regex::new(r"[\d\s]").unwrap();
 }
 
-static MIN_VALID_LEN: usize = 3;
-static MIN_LEN: usize = 4;
-static MAX_LEN: usize = 10;
-static MAX_LEN_DIFFERENCE: usize = 5;
+fn normalize(word: &str, locale: &Locale) -> Normalized {
+  lazy_static! {
+    static ref NORMAL_RE: Regex = Regex::new(r"\W+").unwrap();
+  }
 
-#[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
-struct Normalized(String); // Used as a string with nonword characters stripped
-#[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
-struct Depermuted(String); // Used as a Normalized with its characters sorted
+  let lower = locale.to_lowercase(&locale.compose(word));
+  Normalized(NORMAL_RE.replace_all(&lower, "").into_owned())
+}
 
 struct Stage1 {
   permutations: HashMap<Depermuted, HashSet<Normalized>>,
-  counts: HashMap<Depermuted, CharCounts>,
-  valid_subwords: HashSet<Depermuted>,
+  bonus_permutations: HashMap<Depermuted, HashSet<Normalized>>, // Words that can only ever be bonus words
+  subword_index: SubsetIndex, // Indexes the keys of permutations
+  bonus_index: SubsetIndex,   // Indexes the keys of bonus_permutations
   len_groups: HashMap<usize, HashSet<Depermuted>>,
   forms: HashMap<Normalized, Vec<WordlistForm>>,
 }
 
 struct Stage2<'a> {
   sets: HashMap<Depermuted, Vec<Normalized>>, // TODO: can I go back to borrowing inside the vec?
+  bonus_sets: HashMap<Depermuted, Vec<Normalized>>,
   set_keys: HashMap<usize, Vec<&'a Depermuted>>,
   used_words: HashSet<Normalized>,
 }
 
-fn stage_1(file: &str, blacklist_file: &str) -> Result<Stage1> {
-  let words: BTreeSet<_> = {
-    let file = BufReader::new(File::open(file)?);
-
-    file
-      .lines()
-      .map(|l| l.unwrap().trim().to_string())
-      .collect()
-  };
+fn stage_1(
+  words: Vec<String>,
+  bonus_file: Option<&str>,
+  profile: &Profile,
+  report: &mut Report,
+) -> Result<Stage1> {
+  let locale = profile.locale();
+
+  // Maps each word to whether it should only be used as a bonus word.  Words
+  // are composed first, so each letter is blanked and counted only once.
+  let mut words: BTreeMap<String, bool> = words
+    .iter()
+    .map(|w| (locale.compose(w), false))
+    .collect();
 
   println!("read {} word(s)", words.len());
 
-  let blacklist: HashSet<_> = {
-    let file = BufReader::new(File::open(blacklist_file)?);
+  report.input.words = words.len();
+
+  if let Some(bonus_file) = bonus_file {
+    let file = BufReader::new(File::open(bonus_file)?);
+    let mut count = 0;
+
+    for line in file.lines() {
+      let word = locale.compose(line?.trim());
 
-    lazy_static! {
-      static ref COMMENT_RE: Regex = Regex::new(r"^\s*#").unwrap();
+      if !words.contains_key(&word) {
+        words.insert(word, true);
+        count = count + 1;
+      }
     }
 
-    file
-      .lines()
-      .map(|l| l.unwrap())
-      .filter_map(|l| {
-        if COMMENT_RE.is_match(&l) {
-          None
-        } else {
-          Some(l.trim().to_string())
-        }
-      })
-      .collect()
-  };
+    println!("read {} bonus word(s)", count);
+
+    report.input.bonus_words = count;
+  }
 
-  println!("read {} blacklisted word(s)", blacklist.len());
+  let blacklist = Blacklist::new(profile)?;
+
+  for category in &blacklist.categories {
+    println!(
+      "read {} blacklist rule(s) from {}",
+      category.rules.len(),
+      category.name
+    );
+
+    report
+      .blacklist
+      .categories
+      .insert(category.name.clone(), category.rules.len());
+  }
+
+  if !blacklist.whitelist.is_empty() {
+    println!("read {} whitelisted word(s)", blacklist.whitelist.len());
+  }
+
+  report.blacklist.read = blacklist.len();
 
   let mut permutations: HashMap<Depermuted, HashSet<Normalized>> =
     HashMap::new();
-  let mut counts: HashMap<Depermuted, CharCounts> = HashMap::new();
+  let mut bonus_permutations: HashMap<Depermuted, HashSet<Normalized>> =
+    HashMap::new();
   let mut len_groups: HashMap<usize, HashSet<Depermuted>> = HashMap::new();
   let mut valid_subwords: HashSet<Depermuted> = HashSet::new();
 
   let mut forms: HashMap<Normalized, Vec<WordlistForm>> = HashMap::new();
 
-  let mut used_blacklist: BTreeSet<Normalized> = BTreeSet::new();
+  // The words blacklisted by each rule, and those the whitelist let through
+  let mut used_blacklist: BTreeMap<blacklist::Match, BTreeSet<Normalized>> =
+    BTreeMap::new();
+  let mut whitelisted: BTreeSet<Normalized> = BTreeSet::new();
 
   lazy_static! {
-    static ref REJECT_RE: Regex = Regex::new(r"[\d\s]").unwrap();
-    static ref NORMAL_RE: Regex = Regex::new(r"\W+").unwrap();
     static ref BLANK_RE: Regex = Regex::new(r"[\w--\p{Lu}\p{Lt}]").unwrap();
     static ref BLANK_CAPS_RE: Regex = Regex::new(r"[\p{Lu}\p{Lt}]").unwrap();
   }
 
-  let blacklist: HashSet<_> = blacklist
-    .iter()
-    .map(|w| {
-      let lower = w.to_lowercase();
-      Normalized(NORMAL_RE.replace_all(&lower, "").into_owned())
-    })
-    .collect();
-
-  for word in words {
+  for (word, bonus) in words {
     use std::collections::hash_map::Entry::*;
 
     if REJECT_RE.is_match(&word) {
+      report.input.rejected.push(word);
       continue;
     }
 
-    let normalized = word.to_lowercase();
-    let normalized =
-      Normalized(NORMAL_RE.replace_all(&normalized, "").into_owned());
+    let normalized = normalize(&word, &locale);
 
-    if blacklist.contains(&normalized) {
-      used_blacklist.insert(normalized.clone());
-      continue;
+    match blacklist.verdict(&normalized) {
+      Verdict::Blacklisted(m) => {
+        used_blacklist
+          .entry(m)
+          .or_insert_with(BTreeSet::new)
+          .insert(normalized);
+        continue;
+      }
+      Verdict::Whitelisted => {
+        whitelisted.insert(normalized.clone());
+      }
+      Verdict::Clean => {}
     }
 
     let blank = BLANK_RE.replace_all(&word, "_");
@@ -191,16 +280,30 @@ fn stage_1(file: &str, blacklist_file: &str) -> Result<Stage1> {
       full: word.clone(),
     });
 
-    let mut depermuted: Vec<_> = normalized.0.chars().collect();
-    depermuted.sort();
-    let depermuted = Depermuted(depermuted.into_iter().collect());
+    let depermuted = subset::depermute(&normalized.0);
+
+    if bonus || depermuted.len() < profile.min_valid_len {
+      if depermuted.len() < MIN_BONUS_LEN {
+        continue;
+      }
+
+      match bonus_permutations.entry(depermuted.clone()) {
+        Vacant(v) => {
+          v.insert(HashSet::new()).insert(normalized);
+        }
+        Occupied(o) => {
+          o.into_mut().insert(normalized);
+        }
+      }
+
+      continue;
+    }
 
     match permutations.entry(depermuted.clone()) {
       Vacant(v) => {
         v.insert(HashSet::new()).insert(normalized);
-        counts.insert(depermuted.clone(), count_chars(&depermuted.0));
 
-        match len_groups.entry(depermuted.0.len()) {
+        match len_groups.entry(depermuted.len()) {
           Vacant(v) => {
             v.insert(HashSet::new()).insert(depermuted.clone());
           }
@@ -209,9 +312,7 @@ fn stage_1(file: &str, blacklist_file: &str) -> Result<Stage1> {
           }
         }
 
-        if depermuted.0.len() >= MIN_VALID_LEN {
-          valid_subwords.insert(depermuted);
-        }
+        valid_subwords.insert(depermuted);
       }
       Occupied(o) => {
         o.into_mut().insert(normalized);
@@ -222,85 +323,137 @@ fn stage_1(file: &str, blacklist_file: &str) -> Result<Stage1> {
   println!("{} normalized", forms.len());
   println!("{} depermuted", permutations.len());
   println!("{} valid subword(s)", valid_subwords.len());
+  println!("{} bonus depermuted", bonus_permutations.len());
+
+  report.stage_1 = report::Stage1Counts {
+    normalized: forms.len(),
+    depermuted: permutations.len(),
+    valid_subwords: valid_subwords.len(),
+    bonus_depermuted: bonus_permutations.len(),
+  };
 
   {
     let used = used_blacklist;
-    let unused: BTreeSet<_> =
-      blacklist.iter().filter(|w| !used.contains(w)).collect();
-
-    println!("performing extra blacklist checks...");
+    let mut unused: Vec<&str> = Vec::new();
 
-    let mut maybe: BTreeMap<&Normalized, BTreeSet<&String>> = BTreeMap::new();
+    for category in &blacklist.categories {
+      for (line, _) in &category.rules {
+        let m = blacklist::Match {
+          category: &category.name,
+          rule: line,
+        };
 
-    for (p, fs) in forms.iter().filter_map(|(n, fs)| {
-      if used.contains(n) {
-        return None;
+        if !used.contains_key(&m) {
+          unused.push(line);
+        }
       }
+    }
 
-      if let Some(p) = blacklist.iter().find(|p| n.0.contains(&p.0)) {
-        Some((p, fs))
-      } else {
-        None
-      }
-    }) {
-      use std::collections::btree_map::Entry::*;
+    println!("performing extra blacklist checks...");
+
+    let mut maybe: BTreeMap<&Normalized, BTreeSet<&String>> = BTreeMap::new();
 
-      let mut set = match maybe.entry(p) {
-        Vacant(v) => v.insert(BTreeSet::new()),
-        Occupied(o) => o.into_mut(),
-      };
+    for (n, fs) in &forms {
+      if let Some((_, p)) = blacklist.suspect(n) {
+        let set = maybe.entry(p).or_insert_with(BTreeSet::new);
 
-      for f in fs {
-        set.insert(&f.full);
+        for f in fs {
+          set.insert(&f.full);
+        }
       }
     }
 
     println!(
-      "{} blacklisted used, {} unused",
+      "{} blacklist rule(s) used, {} unused, {} word(s) whitelisted",
       used.len(),
-      unused.len()
+      unused.len(),
+      whitelisted.len()
     );
 
-    {
-      let mut file = File::create("usblk.log")?;
-
-      for word in used {
-        writeln!(file, "{}", word.0)?;
-      }
+    if !maybe.is_empty() {
+      println!(
+        "{} blacklisted word(s) appear inside others, see review",
+        maybe.len()
+      );
     }
 
-    {
-      let mut file = File::create("unblk.log")?;
-
-      for word in unused {
-        writeln!(file, "{}", word.0)?;
-      }
+    for (m, words) in &used {
+      report
+        .blacklist
+        .used
+        .entry(m.category.to_string())
+        .or_insert_with(BTreeMap::new)
+        .insert(
+          m.rule.to_string(),
+          words.iter().map(|w| w.0.clone()).collect(),
+        );
     }
 
-    {
-      let mut file = File::create("maybeblk.log")?;
-
-      for (blk, words) in maybe {
-        writeln!(file, "{}: ({})", blk.0, words.len())?;
-
-        for word in words {
-          writeln!(file, "  {}", word)?;
-        }
-      }
-    }
+    report.blacklist.unused = unused.iter().map(|r| r.to_string()).collect();
+    report.blacklist.whitelisted =
+      whitelisted.iter().map(|w| w.0.clone()).collect();
+    report.blacklist.maybe = maybe
+      .iter()
+      .map(|(blk, words)| {
+        (blk.0.clone(), words.iter().map(|w| w.to_string()).collect())
+      })
+      .collect();
   }
 
+  let subword_index = SubsetIndex::new(&valid_subwords);
+  let bonus_index = SubsetIndex::new(bonus_permutations.keys());
+
   Ok(Stage1 {
     permutations,
-    counts,
-    valid_subwords,
+    bonus_permutations,
+    subword_index,
+    bonus_index,
     len_groups,
     forms,
   })
 }
 
-fn stage_2<'a>(s1: &'a Arc<Stage1>) -> Result<Stage2<'a>> {
+// Collects the words that can be made from a key's letters, split into those
+// required to clear the level and those only accepted as bonus words
+fn build_set(
+  s1: &Stage1,
+  depermuted: &Depermuted,
+  max_len_difference: usize,
+) -> (Vec<Normalized>, Vec<Normalized>) {
+  let mut list: Vec<Normalized> = Vec::new();
+  let mut bonus: Vec<Normalized> = Vec::new();
+
+  for deperm2 in s1.subword_index.subsets(depermuted) {
+    // Subwords too short to be displayed are still accepted as bonus words
+    if depermuted.len() < max_len_difference
+      || deperm2.len() >= depermuted.len() - max_len_difference
+    {
+      list.extend(s1.permutations[deperm2].iter().cloned()); // TODO: can I go back to borrowing this?
+    } else {
+      bonus.extend(s1.permutations[deperm2].iter().cloned());
+    }
+  }
+
+  for deperm2 in s1.bonus_index.subsets(depermuted) {
+    let norms = &s1.bonus_permutations[deperm2];
+
+    bonus.extend(norms.iter().filter(|n| !list.contains(n)).cloned());
+  }
+
+  list.sort_by(|a, b| a.len().cmp(&b.len()).then(a.0.cmp(&b.0)));
+  bonus.sort_by(|a, b| a.len().cmp(&b.len()).then(a.0.cmp(&b.0)));
+
+  (list, bonus)
+}
+
+fn stage_2<'a>(
+  s1: &'a Arc<Stage1>,
+  profile: &Profile,
+  constraints: &Constraints,
+  report: &mut Report,
+) -> Result<Stage2<'a>> {
   let mut sets: HashMap<Depermuted, Vec<Normalized>> = HashMap::new(); // TODO: can I go back to borrowing inside the vec?
+  let mut bonus_sets: HashMap<Depermuted, Vec<Normalized>> = HashMap::new();
   let mut set_keys: HashMap<usize, Vec<&Depermuted>> = HashMap::new();
 
   let mut used_words: HashSet<Normalized> = HashSet::new();
@@ -312,13 +465,15 @@ fn stage_2<'a>(s1: &'a Arc<Stage1>) -> Result<Stage2<'a>> {
 
   let start = Instant::now();
 
+  let max_len_difference = profile.max_len_difference;
+
   let worker: ThreadPool<_> = ThreadPool::new(
-    (0..10)
+    (0..profile.workers)
       .map(|_| (Arc::clone(s1), done.clone(), total.clone(), set_tx.clone()))
       .collect(),
-    |_id,
-     (s1, done, total, set_tx),
-     (depermuted, count): (Depermuted, CharCounts)| {
+    move |_id,
+      (s1, done, total, set_tx),
+      depermuted: Depermuted| {
       let i = done.fetch_add(1, Ordering::Relaxed);
       if i % 10 == 0 {
         print!(
@@ -330,33 +485,26 @@ fn stage_2<'a>(s1: &'a Arc<Stage1>) -> Result<Stage2<'a>> {
         io::stdout().flush().unwrap();
       }
 
-      let mut list: Vec<_> = s1
-        .valid_subwords
-        .iter()
-        .filter(|deperm2| {
-          deperm2.0.len() <= depermuted.0.len()
-            && (depermuted.0.len() < MAX_LEN_DIFFERENCE
-              || deperm2.0.len() >= depermuted.0.len() - MAX_LEN_DIFFERENCE)
-            && is_subseq(&s1.counts[*deperm2], &count)
-        })
-        .flat_map(|d| s1.permutations[d].clone()) // TODO: can I go back to borrowing this?
-        .collect();
-
-      list.sort_by(|a, b| a.0.len().cmp(&b.0.len()).then(a.0.cmp(&b.0)));
+      let (list, bonus) = build_set(&s1, &depermuted, max_len_difference);
 
       set_tx
-        .send((depermuted, list))
+        .send((depermuted, list, bonus))
         .expect("failed to send result");
     },
   );
 
-  for len in MIN_LEN..MAX_LEN + 1 {
+  for len in profile.min_len..profile.max_len + 1 {
     let mut keys: Vec<&Depermuted> = Vec::new();
 
-    total.fetch_add(s1.len_groups[&len].len(), Ordering::Relaxed);
+    let group = match s1.len_groups.get(&len) {
+      Some(g) => g,
+      None => continue, // No words this long
+    };
 
-    for (_, depermuted) in s1.len_groups[&len].iter().enumerate() {
-      worker.queue((depermuted.clone(), s1.counts[depermuted].clone()));
+    total.fetch_add(group.len(), Ordering::Relaxed);
+
+    for (_, depermuted) in group.iter().enumerate() {
+      worker.queue(depermuted.clone());
       keys.push(depermuted);
     }
 
@@ -375,21 +523,378 @@ fn stage_2<'a>(s1: &'a Arc<Stage1>) -> Result<Stage2<'a>> {
     time.subsec_millis() / 10
   );
 
-  for (depermuted, list) in set_rx.try_iter() {
-    for norm in &list {
+  let mut rejected: BTreeMap<Reject, usize> = BTreeMap::new();
+
+  for (depermuted, list, bonus) in set_rx.try_iter() {
+    if let Some(reason) = constraints.check(&depermuted, &list) {
+      let count = rejected.entry(reason).or_insert(0);
+      *count = *count + 1;
+      continue;
+    }
+
+    for norm in list.iter().chain(&bonus) {
       used_words.insert(Normalized::clone(norm));
     }
 
+    bonus_sets.insert(depermuted.clone(), bonus);
     sets.insert(depermuted, list);
   }
 
+  for keys in set_keys.values_mut() {
+    keys.retain(|k| sets.contains_key(*k));
+  }
+
+  println!(
+    "{} key(s) kept, {} dropped",
+    sets.len(),
+    rejected.values().fold(0, |s, n| s + n)
+  );
+
+  for (reason, count) in &rejected {
+    println!("  {}: {}", reason, count);
+  }
+
+  report.stage_2.processed = done.load(Ordering::Acquire);
+  report.stage_2.kept = sets.len();
+  report.stage_2.dropped = rejected
+    .iter()
+    .map(|(reason, count)| (reason.to_string(), *count))
+    .collect();
+
+  for (key, list) in &sets {
+    let sizes = report
+      .stage_2
+      .set_sizes
+      .entry(key.len())
+      .or_insert_with(BTreeMap::new);
+    let count = sizes.entry(list.len()).or_insert(0);
+
+    *count = *count + 1;
+  }
+
   Ok(Stage2 {
     sets,
+    bonus_sets,
     set_keys,
     used_words,
   })
 }
 
+// Reads either plain word<TAB>definition lines or WordNet data files
+fn read_definitions(
+  file: &str,
+  locale: &Locale,
+) -> Result<HashMap<Normalized, Vec<String>>> {
+  let file = BufReader::new(File::open(file)?);
+
+  lazy_static! {
+    static ref WORDNET_RE: Regex =
+      Regex::new(r"^\d{8} \d{2} [nvasr] ([0-9a-f]{2}) (.*?) \| (.*)$").unwrap();
+    static ref WORDNET_MARKER_RE: Regex = Regex::new(r"\([a-z]+\)$").unwrap();
+  }
+
+  let mut defs: HashMap<Normalized, Vec<String>> = HashMap::new();
+
+  let mut add = |word: &str, def: &str| {
+    use std::collections::hash_map::Entry::*;
+
+    let def = def.trim();
+
+    if def.is_empty() {
+      return;
+    }
+
+    let list = match defs.entry(normalize(word, locale)) {
+      Vacant(v) => v.insert(Vec::new()),
+      Occupied(o) => o.into_mut(),
+    };
+
+    if !list.iter().any(|d| d == def) {
+      list.push(def.to_string());
+    }
+  };
+
+  for line in file.lines() {
+    let line = line?;
+
+    if let Some(caps) = WORDNET_RE.captures(&line) {
+      let count = usize::from_str_radix(&caps[1], 16).unwrap();
+
+      // Each word is followed by its lex_id, which we don't care about
+      for word in caps[2].split_whitespace().step_by(2).take(count) {
+        let word = WORDNET_MARKER_RE.replace(word, "").replace('_', " ");
+
+        add(&word, &caps[3]);
+      }
+    } else {
+      let mut parts = line.splitn(2, '\t');
+
+      match (parts.next(), parts.next()) {
+        (Some(word), Some(def)) => add(word.trim(), def),
+        _ => continue,
+      }
+    }
+  }
+
+  Ok(defs)
+}
+
+// Counts how often each letter follows every context of up to MODEL_ORDER
+// letters across the whole word list, for the game to shuffle with
+fn build_model<'a, I>(words: I) -> BTreeMap<(String, String), f64>
+where
+  I: IntoIterator<Item = &'a Normalized>,
+{
+  let mut model = BTreeMap::new();
+
+  for word in words {
+    let chars: Vec<char> = word.0.chars().collect();
+
+    for i in 1..chars.len() {
+      for n in 1..cmp::min(MODEL_ORDER, i) + 1 {
+        let context: String = chars[i - n..i].iter().collect();
+
+        let weight =
+          model.entry((context, chars[i].to_string())).or_insert(0.0);
+
+        *weight = *weight + 1.0;
+      }
+    }
+  }
+
+  model
+}
+
+fn database_url() -> Result<String> {
+  use dotenv::dotenv;
+
+  dotenv().ok();
+
+  Ok(env::var("DATABASE_URL")?)
+}
+
+// A build writes a temporary file next to the database and renames it into
+// place, which can't be done with URIs or in-memory databases
+fn file_path(url: &str) -> Result<&str> {
+  if url.is_empty() || url == ":memory:" || url.starts_with("file:") {
+    return Err(ErrorKind::DatabaseUrl(url.into()).into());
+  }
+
+  Ok(url)
+}
+
+fn establish() -> Result<diesel::sqlite::SqliteConnection> {
+  use diesel::{prelude::*, sqlite::SqliteConnection};
+
+  Ok(SqliteConnection::establish(&database_url()?)?)
+}
+
+// The migrations a new database is created with, in order, along with the
+// versions Diesel records them under
+static MIGRATIONS: [(&str, &str); 5] = [
+  (
+    "20180919233955",
+    include_str!("../migrations/2018-09-19-233955_init_wordlist/up.sql"),
+  ),
+  (
+    "20181113000000",
+    include_str!("../migrations/2018-11-13-000000_add_bonuses/up.sql"),
+  ),
+  (
+    "20181114000000",
+    include_str!("../migrations/2018-11-14-000000_add_definitions/up.sql"),
+  ),
+  (
+    "20181115000000",
+    include_str!("../migrations/2018-11-15-000000_add_lookup_indexes/up.sql"),
+  ),
+  (
+    "20181116000000",
+    include_str!("../migrations/2018-11-16-000000_add_transitions/up.sql"),
+  ),
+];
+
+// Runs every migration on a new database, recording them as Diesel would so
+// `diesel migration run` doesn't try to run them again
+fn create_schema(conn: &diesel::sqlite::SqliteConnection) -> Result<()> {
+  use diesel::{connection::SimpleConnection, prelude::*, sql_query};
+
+  conn.batch_execute(
+    "CREATE TABLE __diesel_schema_migrations (
+       version VARCHAR(50) PRIMARY KEY NOT NULL,
+       run_on  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+     );",
+  )?;
+
+  for (version, sql) in MIGRATIONS.iter() {
+    conn.batch_execute(sql)?;
+
+    sql_query(format!(
+      "INSERT INTO __diesel_schema_migrations (version) VALUES ('{}')",
+      version
+    )).execute(conn)?;
+  }
+
+  Ok(())
+}
+
+static INSERT_CHUNK: usize = 10000;
+
+// Inserts rows a chunk at a time, so progress can be shown for big tables
+macro_rules! insert_all {
+  ($conn:expr, $table:expr, $name:expr, $rows:expr) => {{
+    let rows = $rows;
+
+    for (i, chunk) in rows.chunks(INSERT_CHUNK).enumerate() {
+      print!(
+        "\r\x1b[2K  {} ({}/{})",
+        $name,
+        cmp::min((i + 1) * INSERT_CHUNK, rows.len()),
+        rows.len()
+      );
+      io::stdout().flush()?;
+
+      insert_into($table).values(chunk).execute($conn)?;
+    }
+
+    println!("\r\x1b[2K  {} ({})", $name, rows.len());
+  }};
+}
+
+// Creates the schema on a new database and fills it with everything a build
+// produced, in one transaction
+fn write_database(
+  conn: &diesel::sqlite::SqliteConnection,
+  forms: &HashMap<Normalized, Vec<WordlistForm>>,
+  s2: &Stage2,
+  defs: &HashMap<Normalized, Vec<String>>,
+  model: &BTreeMap<(String, String), f64>,
+  report: &mut Report,
+) -> Result<()> {
+  use diesel::{insert_into, prelude::*};
+  use models::*;
+
+  println!("collecting models...");
+
+  let mut insert_form_ids: Vec<FormId> = Vec::new();
+  let mut insert_forms: Vec<Form> = Vec::new();
+  let mut insert_set_ids: Vec<SetId> = Vec::new();
+  let mut insert_sets: Vec<Set> = Vec::new();
+  let mut insert_bonuses: Vec<Bonus> = Vec::new();
+  let mut insert_set_keys: Vec<SetKey> = Vec::new();
+  let mut insert_defs: Vec<Definition> = Vec::new();
+  let mut insert_transitions: Vec<Transition> = Vec::new();
+
+  for (i, (norm, forms)) in forms.iter().enumerate() {
+    insert_form_ids.push(FormId {
+      norm: &norm.0,
+      id: i as i32,
+    });
+
+    for form in forms {
+      let oid = insert_forms.len() as i32;
+      insert_forms.push(Form {
+        oid,
+        id: i as i32,
+        blank: &form.blanked,
+        full: &form.full,
+      });
+    }
+  }
+
+  for (i, (deperm, norms)) in s2.sets.iter().enumerate() {
+    insert_set_ids.push(SetId {
+      key: &deperm.0,
+      id: i as i32,
+    });
+
+    for norm in norms {
+      let oid = insert_sets.len() as i32;
+      insert_sets.push(Set {
+        oid,
+        id: i as i32,
+        norm: &norm.0,
+      });
+    }
+
+    for norm in &s2.bonus_sets[deperm] {
+      let oid = insert_bonuses.len() as i32;
+      insert_bonuses.push(Bonus {
+        oid,
+        id: i as i32,
+        norm: &norm.0,
+      });
+    }
+  }
+
+  for (len, deperms) in &s2.set_keys {
+    for deperm in deperms {
+      let oid = insert_set_keys.len() as i32;
+      insert_set_keys.push(SetKey {
+        oid,
+        len: *len as i32,
+        key: &deperm.0,
+      });
+    }
+  }
+
+  for (norm, defs) in defs {
+    for def in defs {
+      let oid = insert_defs.len() as i32;
+      insert_defs.push(Definition {
+        oid,
+        norm: &norm.0,
+        definition: def,
+      });
+    }
+  }
+
+  for ((context, next), weight) in model {
+    let oid = insert_transitions.len() as i32;
+    insert_transitions.push(Transition {
+      oid,
+      context,
+      next,
+      weight: *weight,
+    });
+  }
+
+  println!("committing to database...");
+
+  {
+    use schema::{
+      bonuses::dsl::*, definitions::dsl::*, form_ids::dsl::*, forms::dsl::*,
+      set_ids::dsl::*, set_keys::dsl::*, sets::dsl::*, transitions::dsl::*,
+    };
+
+    conn.transaction::<_, Error, _>(|| {
+      create_schema(conn)?;
+
+      insert_all!(conn, form_ids, "form_ids", &insert_form_ids);
+      insert_all!(conn, forms, "forms", &insert_forms);
+      insert_all!(conn, set_ids, "set_ids", &insert_set_ids);
+      insert_all!(conn, sets, "sets", &insert_sets);
+      insert_all!(conn, bonuses, "bonuses", &insert_bonuses);
+      insert_all!(conn, set_keys, "set_keys", &insert_set_keys);
+      insert_all!(conn, definitions, "definitions", &insert_defs);
+      insert_all!(conn, transitions, "transitions", &insert_transitions);
+
+      Ok(())
+    })?;
+  }
+
+  report.output.insert("form_ids", insert_form_ids.len());
+  report.output.insert("forms", insert_forms.len());
+  report.output.insert("set_ids", insert_set_ids.len());
+  report.output.insert("sets", insert_sets.len());
+  report.output.insert("bonuses", insert_bonuses.len());
+  report.output.insert("set_keys", insert_set_keys.len());
+  report.output.insert("definitions", insert_defs.len());
+  report.output.insert("transitions", insert_transitions.len());
+
+  Ok(())
+}
+
 fn run() -> Result<()> {
   let mut args: VecDeque<_> = env::args().collect();
   args.pop_front(); // drop argv[0]
@@ -408,106 +913,259 @@ fn run() -> Result<()> {
       })
   }
 
-  let file: String = parse_arg(&mut args, "an input filename")?;
+  if args.front().map_or(false, |a| a == "verify") {
+    let conn = establish()?;
 
-  let s1 = Arc::new(stage_1(&file, "etc/blacklist.txt")?);
+    return match verify::verify(&conn)? {
+      0 => {
+        println!("no problems found");
+        Ok(())
+      }
+      n => Err(ErrorKind::Verify(n).into()),
+    };
+  }
 
-  let s2 = stage_2(&s1)?;
+  let command = match args.front().map(|a| a.as_str()) {
+    Some(c @ "bench")
+    | Some(c @ "update")
+    | Some(c @ "import")
+    | Some(c @ "review") => c.to_string(),
+    _ => String::new(),
+  };
 
-  let mut forms = s1.forms.clone();
+  if !command.is_empty() {
+    args.pop_front();
+  }
 
-  forms.retain(|k, _| s2.used_words.contains(k));
+  let file: String = parse_arg(&mut args, "an input path")?;
+
+  let mut bonus_file: Option<String> = None;
+  let mut defs_file: Option<String> = None;
+  let mut added_file: Option<String> = None;
+  let mut removed_file: Option<String> = None;
+  let mut previous_file: Option<String> = None;
+  let mut report_file: Option<String> = None;
+  let mut format = Format::Plain;
+  let mut out_file = "etc/wordlist.txt".to_string();
+  let mut profile = Profile::default();
+
+  // Options are applied in order, so flags after --profile override it
+  while let Some(arg) = args.pop_front() {
+    match arg.as_str() {
+      "--profile" => {
+        profile.load(&parse_arg::<String>(&mut args, "a profile filename")?)?
+      }
+      a if a.starts_with("--") && profile::KEYS.contains(&&a[2..]) => {
+        let val: String = parse_arg(&mut args, "a value")?;
 
-  {
-    use diesel::{insert_into, prelude::*, sqlite::SqliteConnection};
-    use dotenv::dotenv;
-    use models::*;
+        profile.set(&a[2..], &val)?
+      }
+      "--bonus" => {
+        bonus_file = Some(parse_arg(&mut args, "a bonus wordlist filename")?)
+      }
+      "--defs" => {
+        defs_file = Some(parse_arg(&mut args, "a dictionary filename")?)
+      }
+      "--report" => {
+        report_file = Some(parse_arg(&mut args, "a report filename")?)
+      }
+      "--format" => {
+        let expect = "plain, 12dicts or hunspell";
+        let name: String = parse_arg(&mut args, expect)?;
 
-    println!("collecting models...");
+        format = match Format::parse(&name) {
+          Some(f) => f,
+          None => return Err(ErrorKind::InvalidArg(expect.into()).into()),
+        }
+      }
+      "--out" if command == "import" => {
+        out_file = parse_arg(&mut args, "an output filename")?
+      }
+      "--added" if command == "update" => {
+        added_file = Some(parse_arg(&mut args, "a wordlist filename")?)
+      }
+      "--removed" if command == "update" => {
+        removed_file = Some(parse_arg(&mut args, "a wordlist filename")?)
+      }
+      "--previous" if command == "update" => {
+        previous_file = Some(parse_arg(&mut args, "a wordlist filename")?)
+      }
+      a => {
+        return Err(
+          ErrorKind::ArgParse(format!("unexpected argument '{}'", a)).into(),
+        )
+      }
+    }
+  }
 
-    let mut insert_form_ids: Vec<FormId> = Vec::new();
-    let mut insert_forms: Vec<Form> = Vec::new();
-    let mut insert_set_ids: Vec<SetId> = Vec::new();
-    let mut insert_sets: Vec<Set> = Vec::new();
-    let mut insert_set_keys: Vec<SetKey> = Vec::new();
+  profile.validate()?;
 
-    for (i, (norm, forms)) in forms.iter().enumerate() {
-      insert_form_ids.push(FormId {
-        norm: &norm.0,
-        id: i as i32,
-      });
+  // Checked now rather than after the build has already taken its time
+  if command.is_empty() {
+    file_path(&database_url()?)?;
+  }
 
-      for form in forms {
-        let oid = insert_forms.len() as i32;
-        insert_forms.push(Form {
-          oid,
-          id: i as i32,
-          blank: &form.blanked,
-          full: &form.full,
-        });
-      }
+  let mut report = Report::default();
+  let run_start = Instant::now();
+
+  let words = input::read(&file, format, &mut report)?;
+
+  // A locale given explicitly wins over whatever the source says it's in
+  if profile.locale.is_none() {
+    profile.locale = report.input.lang.clone();
+  }
+
+  if let Some(ref locale) = profile.locale {
+    println!("using locale {}", locale);
+  }
+
+  if command == "import" {
+    let mut out = BufWriter::new(File::create(&out_file)?);
+
+    for word in &words {
+      writeln!(out, "{}", word)?;
     }
 
-    for (i, (deperm, norms)) in s2.sets.iter().enumerate() {
-      insert_set_ids.push(SetId {
-        key: &deperm.0,
-        id: i as i32,
-      });
+    println!("wrote {} word(s) to {}", words.len(), out_file);
 
-      for norm in norms {
-        let oid = insert_sets.len() as i32;
-        insert_sets.push(Set {
-          oid,
-          id: i as i32,
-          norm: &norm.0,
-        });
+    for (kind, words) in &report.input.diagnostics {
+      for word in words {
+        println!("{} {}", kind, word);
       }
     }
 
-    for (len, deperms) in &s2.set_keys {
-      for deperm in deperms {
-        let oid = insert_set_keys.len() as i32;
-        insert_set_keys.push(SetKey {
-          oid,
-          len: *len as i32,
-          key: &deperm.0,
-        });
-      }
+    return Ok(());
+  }
+
+  if command == "bench" {
+    return subset::bench(&words, &profile);
+  }
+
+  if command == "review" {
+    return review::review(&words, &profile);
+  }
+
+  // Which words changed has to be worked out before stage 1 takes the list
+  let changed = if command == "update" {
+    let changed = update::changed_words(
+      &words,
+      added_file.as_ref().map(|f| f.as_str()),
+      removed_file.as_ref().map(|f| f.as_str()),
+      previous_file.as_ref().map(|f| f.as_str()),
+      &profile.locale(),
+    )?;
+
+    if changed.is_empty() {
+      return Err(
+        ErrorKind::InvalidArg("--added, --removed or --previous".into()).into(),
+      );
     }
 
-    println!("committing to database...");
+    changed
+  } else {
+    BTreeSet::new()
+  };
+
+  let s1 = Arc::new(stage_1(
+    words,
+    bonus_file.as_ref().map(|f| f.as_str()),
+    &profile,
+    &mut report,
+  )?);
+
+  report.time("stage_1", run_start.elapsed());
+
+  let constraints = Constraints::new(&profile)?;
 
-    dotenv().ok();
+  if command == "update" {
+    println!("{} changed word(s)", changed.len());
 
-    let url = env::var("DATABASE_URL")?;
-    let conn = SqliteConnection::establish(&url)?;
+    let defs = match defs_file {
+      Some(f) => Some(read_definitions(&f, &profile.locale())?),
+      None => None,
+    };
+
+    let conn = establish()?;
 
     let start = Instant::now();
 
-    {
-      use schema::{
-        form_ids::dsl::*, forms::dsl::*, set_ids::dsl::*, set_keys::dsl::*,
-        sets::dsl::*,
-      };
+    update::update(
+      &conn,
+      &s1,
+      &changed,
+      defs.as_ref(),
+      &profile,
+      &constraints,
+    )?;
 
-      println!("  form_ids");
-      insert_into(form_ids)
-        .values(&insert_form_ids)
-        .execute(&conn)?;
+    report.time("update", start.elapsed());
+    report.time("total", run_start.elapsed());
 
-      println!("  forms");
-      insert_into(forms).values(&insert_forms).execute(&conn)?;
+    if let Some(f) = report_file {
+      report.write(&f)?;
+    }
 
-      println!("  set_ids");
-      insert_into(set_ids).values(&insert_set_ids).execute(&conn)?;
+    return Ok(());
+  }
 
-      println!("  sets");
-      insert_into(sets).values(&insert_sets).execute(&conn)?;
+  let start = Instant::now();
 
-      println!("  set_keys");
-      insert_into(set_keys)
-        .values(&insert_set_keys)
-        .execute(&conn)?;
+  let s2 = stage_2(&s1, &profile, &constraints, &mut report)?;
+
+  report.time("stage_2", start.elapsed());
+
+  let start = Instant::now();
+
+  let model = build_model(s1.forms.keys());
+
+  report.time("model", start.elapsed());
+
+  println!("{} letter transition(s)", model.len());
+
+  let mut forms = s1.forms.clone();
+
+  forms.retain(|k, _| s2.used_words.contains(k));
+
+  let defs = match defs_file {
+    Some(f) => {
+      let mut defs = read_definitions(&f, &profile.locale())?;
+
+      defs.retain(|k, _| forms.contains_key(k));
+
+      println!("read definitions for {} word(s)", defs.len());
+
+      defs
+    }
+    None => HashMap::new(),
+  };
+
+  {
+    use diesel::{prelude::*, sqlite::SqliteConnection};
+
+    let url = database_url()?;
+    let path = file_path(&url)?;
+    let temp_path = format!("{}.tmp", path);
+
+    if Path::new(&temp_path).exists() {
+      fs::remove_file(&temp_path)?;
+    }
+
+    let start = Instant::now();
+
+    // Everything goes into a fresh database that only replaces the old one
+    // once it's complete, so a failure can't leave a half-written one behind
+    let result = (|| -> Result<()> {
+      let conn = SqliteConnection::establish(&temp_path)?;
+
+      write_database(&conn, &forms, &s2, &defs, &model, &mut report)
+    })();
+
+    match result {
+      Ok(()) => fs::rename(&temp_path, path)?,
+      Err(e) => {
+        fs::remove_file(&temp_path).ok();
+        return Err(e);
+      }
     }
 
     let end = Instant::now();
@@ -518,6 +1176,14 @@ fn run() -> Result<()> {
       time.as_secs(),
       time.subsec_millis() / 10
     );
+
+    report.time("commit", time);
+  }
+
+  report.time("total", run_start.elapsed());
+
+  if let Some(f) = report_file {
+    report.write(&f)?;
   }
 
   Ok(())
@@ -526,6 +1192,29 @@ fn run() -> Result<()> {
 fn main() {
   match run() {
     Ok(_) => return,
-    Err(e) => writeln!(io::stderr(), "an error occurred: {}", e).unwrap(),
+    Err(e) => {
+      writeln!(io::stderr(), "an error occurred: {}", e).unwrap();
+      std::process::exit(1);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn file_path() {
+    assert_eq!(
+      super::file_path("etc/wordlist.sqlite3").unwrap(),
+      "etc/wordlist.sqlite3"
+    );
+
+    for url in &["", ":memory:", "file:etc/wordlist.sqlite3?mode=rwc"] {
+      match super::file_path(url).map_err(|e| e.0) {
+        Err(ErrorKind::DatabaseUrl(u)) => assert_eq!(&u, url),
+        r => panic!("expected {} to be refused, got {:?}", url, r),
+      }
+    }
   }
 }
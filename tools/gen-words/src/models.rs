@@ -58,6 +58,21 @@ pub struct SetQ {
   pub norm: String,
 }
 
+#[derive(Insertable)]
+#[table_name = "bonuses"]
+pub struct Bonus<'a> {
+  pub oid: i32,
+  pub id: i32,
+  pub norm: &'a str,
+}
+
+#[derive(Queryable)]
+pub struct BonusQ {
+  pub oid: i32,
+  pub id: i32,
+  pub norm: String,
+}
+
 #[derive(Insertable)]
 #[table_name = "set_keys"]
 pub struct SetKey<'a> {
@@ -66,9 +81,34 @@ pub struct SetKey<'a> {
   pub key: &'a str,
 }
 
+#[derive(Insertable)]
+#[table_name = "definitions"]
+pub struct Definition<'a> {
+  pub oid: i32,
+  pub norm: &'a str,
+  pub definition: &'a str,
+}
+
 #[derive(Queryable)]
-pub struct SetKeyQ {
+pub struct DefinitionQ {
   pub oid: i32,
-  pub len: i32,
-  pub key: String,
+  pub norm: String,
+  pub definition: String,
+}
+
+#[derive(Insertable)]
+#[table_name = "transitions"]
+pub struct Transition<'a> {
+  pub oid: i32,
+  pub context: &'a str,
+  pub next: &'a str,
+  pub weight: f64,
+}
+
+#[derive(Queryable)]
+pub struct TransitionQ {
+  pub oid: i32,
+  pub context: String,
+  pub next: String,
+  pub weight: f64,
 }
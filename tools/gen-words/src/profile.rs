@@ -0,0 +1,303 @@
+use super::*;
+
+// Everything that controls which words and sets end up in a database.  These
+// can be read from a profile file, and then overridden on the command line.
+#[derive(Clone, Debug)]
+pub struct Profile {
+  pub min_valid_len: usize,      // Shorter words can only be bonus words
+  pub min_len: usize,            // Shortest key to generate a set for
+  pub max_len: usize,            // Longest key to generate a set for
+  pub max_len_difference: usize, // Shorter words in a set are bonus words
+  pub blacklist: String, // A file, or a directory of category files
+  pub blacklist_categories: Vec<String>, // Categories to use, or empty for all
+  pub whitelist: Option<String>, // Words never to blacklist
+  pub workers: usize,
+  pub min_set_size: usize, // Fewest words a level can require
+  pub max_set_size: usize, // Most words a level can require, or 0 for any
+  pub min_per_len: usize,  // Fewest words of each length shorter than the key
+  pub common_words: Option<String>, // A full-length word must be on this list
+  pub locale: Option<String>, // Language rules for normalizing, like tr_TR
+}
+
+impl Default for Profile {
+  fn default() -> Self {
+    Self {
+      min_valid_len: 3,
+      min_len: 4,
+      max_len: 10,
+      max_len_difference: 5,
+      blacklist: "etc/blacklist".into(),
+      blacklist_categories: Vec::new(),
+      whitelist: None,
+      workers: 10,
+      min_set_size: 1,
+      max_set_size: 0,
+      min_per_len: 0,
+      common_words: None,
+      locale: None,
+    }
+  }
+}
+
+// The names accepted both as profile keys and (with a leading --) as flags
+pub static KEYS: [&str; 13] = [
+  "min-valid-len",
+  "min-len",
+  "max-len",
+  "max-len-difference",
+  "blacklist",
+  "blacklist-categories",
+  "whitelist",
+  "workers",
+  "min-set-size",
+  "max-set-size",
+  "min-per-len",
+  "common-words",
+  "locale",
+];
+
+impl Profile {
+  // Sets a single value, as named in KEYS
+  pub fn set(&mut self, key: &str, val: &str) -> Result<()> {
+    self
+      .set_impl(key, val)
+      .map_err(|e| ErrorKind::InvalidProfile(e).into())
+  }
+
+  fn set_impl(
+    &mut self,
+    key: &str,
+    val: &str,
+  ) -> ::std::result::Result<(), String> {
+    let num = |val: &str| {
+      val
+        .parse()
+        .map_err(|e: std::num::ParseIntError| format!("{}: {}", key, e))
+    };
+
+    match key {
+      "min-valid-len" => self.min_valid_len = num(val)?,
+      "min-len" => self.min_len = num(val)?,
+      "max-len" => self.max_len = num(val)?,
+      "max-len-difference" => self.max_len_difference = num(val)?,
+      "blacklist" => self.blacklist = val.into(),
+      "blacklist-categories" => {
+        self.blacklist_categories = val
+          .split(',')
+          .map(|c| c.trim().to_string())
+          .filter(|c| !c.is_empty())
+          .collect()
+      }
+      "whitelist" => {
+        self.whitelist = if val.is_empty() {
+          None
+        } else {
+          Some(val.into())
+        }
+      }
+      "workers" => self.workers = num(val)?,
+      "min-set-size" => self.min_set_size = num(val)?,
+      "max-set-size" => self.max_set_size = num(val)?,
+      "min-per-len" => self.min_per_len = num(val)?,
+      "common-words" => {
+        self.common_words = if val.is_empty() {
+          None
+        } else {
+          Some(val.into())
+        }
+      }
+      "locale" => {
+        self.locale = if val.is_empty() {
+          None
+        } else {
+          Some(val.into())
+        }
+      }
+      k => return Err(format!("unknown key '{}'", k)),
+    }
+
+    Ok(())
+  }
+
+  // Reads lines of the form key = value on top of the current values, with
+  // blank lines and lines starting with # ignored
+  pub fn load(&mut self, file: &str) -> Result<()> {
+    let file = BufReader::new(File::open(file)?);
+
+    lazy_static! {
+      static ref LINE_RE: Regex =
+        Regex::new(r"^\s*([\w-]+)\s*=\s*(.*?)\s*$").unwrap();
+      static ref SKIP_RE: Regex = Regex::new(r"^\s*(#|$)").unwrap();
+    }
+
+    for (i, line) in file.lines().enumerate() {
+      let line = line?;
+
+      if SKIP_RE.is_match(&line) {
+        continue;
+      }
+
+      match LINE_RE.captures(&line) {
+        Some(caps) => self.set_impl(&caps[1], &caps[2]).map_err(|e| {
+          Error::from(ErrorKind::InvalidProfile(format!(
+            "line {}: {}",
+            i + 1,
+            e
+          )))
+        })?,
+        None => {
+          return Err(
+            ErrorKind::InvalidProfile(format!(
+              "line {} isn't of the form key = value",
+              i + 1
+            )).into(),
+          )
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  pub fn locale(&self) -> Locale {
+    Locale::new(self.locale.as_ref().map(|l| l.as_str()))
+  }
+
+  pub fn validate(&self) -> Result<()> {
+    let err = |msg: &str| Err(ErrorKind::InvalidProfile(msg.into()).into());
+
+    if self.min_valid_len < MIN_BONUS_LEN {
+      return err("min-valid-len is shorter than the shortest bonus word");
+    }
+
+    if self.min_len < self.min_valid_len {
+      return err("min-len is shorter than min-valid-len");
+    }
+
+    if self.max_len < self.min_len {
+      return err("max-len is shorter than min-len");
+    }
+
+    if self.workers == 0 {
+      return err("workers must be positive");
+    }
+
+    if self.max_set_size > 0 && self.max_set_size < self.min_set_size {
+      return err("max-set-size is smaller than min-set-size");
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn load(name: &str) -> Result<Profile> {
+    let mut profile = Profile::default();
+
+    profile.load(&format!(
+      "{}/tests/fixtures/profile/{}.profile",
+      env!("CARGO_MANIFEST_DIR"),
+      name
+    ))?;
+
+    Ok(profile)
+  }
+
+  fn invalid(result: Result<Profile>) -> String {
+    match result.map(|_| ()).map_err(|e| e.0) {
+      Err(ErrorKind::InvalidProfile(msg)) => msg,
+      r => panic!("expected an invalid profile, got {:?}", r),
+    }
+  }
+
+  #[test]
+  fn loads_over_defaults() {
+    let profile = load("overrides").unwrap();
+
+    assert_eq!(profile.max_len, 7);
+    assert_eq!(profile.blacklist_categories, vec!["slurs", "drugs"]);
+    assert_eq!(profile.whitelist, None);
+
+    // Keys the file leaves out keep their defaults
+    assert_eq!(profile.min_len, Profile::default().min_len);
+    assert_eq!(profile.workers, Profile::default().workers);
+  }
+
+  #[test]
+  fn loads_shipped_profiles() {
+    for name in &["kids", "hard"] {
+      let mut profile = Profile::default();
+      let file =
+        format!("{}/profiles/{}.profile", env!("CARGO_MANIFEST_DIR"), name);
+
+      profile.load(&file).unwrap();
+      profile.validate().unwrap();
+    }
+  }
+
+  #[test]
+  fn unknown_key() {
+    assert_eq!(
+      invalid(load("unknown-key")),
+      "line 2: unknown key 'max-length'"
+    );
+  }
+
+  #[test]
+  fn bad_value() {
+    assert!(invalid(load("bad-value")).starts_with("line 2: workers: "));
+  }
+
+  #[test]
+  fn missing_value() {
+    assert_eq!(
+      invalid(load("no-value")),
+      "line 2 isn't of the form key = value"
+    );
+  }
+
+  #[test]
+  fn missing_file() {
+    assert!(load("missing").is_err());
+  }
+
+  #[test]
+  fn set() {
+    let mut profile = Profile::default();
+
+    profile.set("locale", "tr_TR").unwrap();
+    assert_eq!(profile.locale, Some("tr_TR".to_string()));
+    profile.set("locale", "").unwrap();
+    assert_eq!(profile.locale, None);
+
+    assert!(profile.set("min-len", "-1").is_err());
+    assert!(profile.set("colour", "red").is_err());
+  }
+
+  #[test]
+  fn validate() {
+    assert!(Profile::default().validate().is_ok());
+
+    let bad = |f: &Fn(&mut Profile)| {
+      let mut profile = Profile::default();
+      f(&mut profile);
+      profile.validate().is_err()
+    };
+
+    assert!(bad(&|p| p.min_valid_len = MIN_BONUS_LEN - 1));
+    assert!(bad(&|p| p.min_len = p.min_valid_len - 1));
+    assert!(bad(&|p| p.max_len = p.min_len - 1));
+    assert!(bad(&|p| p.workers = 0));
+    assert!(bad(&|p| {
+      p.min_set_size = 5;
+      p.max_set_size = 4
+    }));
+    assert!(!bad(&|p| {
+      p.min_set_size = 5;
+      p.max_set_size = 0
+    }));
+  }
+}
@@ -0,0 +1,72 @@
+use super::*;
+use std::time::Duration;
+
+// A record of everything a build did, written out as JSON so builds can be
+// compared against each other
+#[derive(Default, Serialize)]
+pub struct Report {
+  pub input: Input,
+  pub blacklist: Blacklist,
+  pub stage_1: Stage1Counts,
+  pub stage_2: Stage2Counts,
+  pub output: BTreeMap<&'static str, usize>, // Rows written to each table
+  pub timing: BTreeMap<&'static str, f64>,   // Seconds spent on each step
+}
+
+#[derive(Default, Serialize)]
+pub struct Input {
+  pub words: usize,
+  pub bonus_words: usize,
+  pub rejected: Vec<String>, // Words with digits or spaces
+  // Anything the source noted about particular words, by kind
+  pub diagnostics: BTreeMap<String, Vec<String>>,
+  pub unrejected: Vec<String>, // Words the source would reject, but never saw
+  pub lang: Option<String>,      // The language the source says it's in
+}
+
+#[derive(Default, Serialize)]
+pub struct Blacklist {
+  pub read: usize,
+  pub categories: BTreeMap<String, usize>, // Rules read from each category
+  pub whitelisted: Vec<String>, // Blacklisted words the whitelist overrode
+  // Words blacklisted, by category and then by the rule that matched them
+  pub used: BTreeMap<String, BTreeMap<String, Vec<String>>>,
+  pub unused: Vec<String>, // Rules that matched nothing
+  // Words containing a blacklisted word, which might need blacklisting too
+  pub maybe: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Default, Serialize)]
+pub struct Stage1Counts {
+  pub normalized: usize,
+  pub depermuted: usize,
+  pub valid_subwords: usize,
+  pub bonus_depermuted: usize,
+}
+
+#[derive(Default, Serialize)]
+pub struct Stage2Counts {
+  pub processed: usize,
+  pub kept: usize,
+  pub dropped: BTreeMap<String, usize>,
+  // For each key length, how many sets require each number of words
+  pub set_sizes: BTreeMap<usize, BTreeMap<usize, usize>>,
+}
+
+impl Report {
+  pub fn time(&mut self, step: &'static str, time: Duration) {
+    let secs = time.as_secs() as f64 + time.subsec_nanos() as f64 / 1e9;
+
+    self.timing.insert(step, secs);
+  }
+
+  pub fn write(&self, path: &str) -> Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+
+    serde_json::to_writer_pretty(file, self)?;
+
+    println!("wrote report to {}", path);
+
+    Ok(())
+  }
+}
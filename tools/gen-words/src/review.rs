@@ -0,0 +1,171 @@
+use super::*;
+use blacklist::{self, Category, Entry};
+
+enum Decision {
+  Next,
+  Quit,
+}
+
+fn prompt(msg: &str) -> Result<Option<String>> {
+  print!("{} ", msg);
+  io::stdout().flush()?;
+
+  let mut line = String::new();
+
+  if io::stdin().read_line(&mut line)? == 0 {
+    println!();
+    return Ok(None); // Treat the end of input like quitting
+  }
+
+  Ok(Some(line.trim().to_lowercase()))
+}
+
+fn review_each(
+  category: &Category,
+  words: &BTreeMap<&Normalized, BTreeSet<&str>>,
+  added: &mut usize,
+) -> Result<Decision> {
+  for (norm, forms) in words {
+    let forms: Vec<&str> = forms.iter().cloned().collect();
+
+    loop {
+      let msg = format!(
+        "  {}: [b]lacklist, e[x]cept, [n]ext, [q]uit?",
+        forms.join(", ")
+      );
+
+      let line = match prompt(&msg)? {
+        Some(l) => l,
+        None => return Ok(Decision::Quit),
+      };
+
+      let entry = match line.as_str() {
+        "b" => Some(Entry::Exact),
+        "x" => Some(Entry::Exception),
+        "n" | "" => None,
+        "q" => return Ok(Decision::Quit),
+        _ => continue,
+      };
+
+      if let Some(entry) = entry {
+        blacklist::write_entry(&mut category.append()?, entry, &norm.0)?;
+        *added = *added + 1;
+      }
+
+      break;
+    }
+  }
+
+  Ok(Decision::Next)
+}
+
+// Walks through every blacklisted word found inside words that weren't
+// blacklisted, appending a rule or exception to its category for each decision
+pub fn review(words: &[String], profile: &Profile) -> Result<()> {
+  let locale = profile.locale();
+  let blacklist = Blacklist::new(profile)?;
+
+  // Maps each suspect part and its category to the words containing it
+  let mut found: BTreeMap<
+    (&str, &Normalized),
+    (&Category, BTreeMap<Normalized, BTreeSet<String>>),
+  > = BTreeMap::new();
+
+  for word in words {
+    let word = locale.compose(word);
+
+    if REJECT_RE.is_match(&word) {
+      continue;
+    }
+
+    let normalized = normalize(&word, &locale);
+
+    if blacklist.check(&normalized).is_some() {
+      continue;
+    }
+
+    if let Some((category, part)) = blacklist.suspect(&normalized) {
+      found
+        .entry((category.name.as_str(), part))
+        .or_insert_with(|| (category, BTreeMap::new()))
+        .1
+        .entry(normalized)
+        .or_insert_with(BTreeSet::new)
+        .insert(word);
+    }
+  }
+
+  println!(
+    "{} blacklisted word(s) appear inside others, in {} word(s)",
+    found.len(),
+    found.values().map(|(_, w)| w.len()).sum::<usize>()
+  );
+
+  let mut added = 0;
+
+  for (i, ((name, part), (category, words))) in found.iter().enumerate() {
+    let words: BTreeMap<&Normalized, BTreeSet<&str>> = words
+      .iter()
+      .map(|(n, fs)| (n, fs.iter().map(|f| f.as_str()).collect()))
+      .collect();
+
+    println!();
+    println!(
+      "({}/{}) '{}' ({}) appears in {} word(s):",
+      i + 1,
+      found.len(),
+      part.0,
+      name,
+      words.len()
+    );
+
+    for forms in words.values() {
+      let forms: Vec<&str> = forms.iter().cloned().collect();
+
+      println!("  {}", forms.join(", "));
+    }
+
+    let decision = loop {
+      let line = match prompt(
+        "[s]ubstring (blacklist all), e[x]cept all, [o]ne by one, [n]ext, \
+         [q]uit?",
+      )? {
+        Some(l) => l,
+        None => break Decision::Quit,
+      };
+
+      match line.as_str() {
+        "s" => {
+          blacklist::write_entry(
+            &mut category.append()?,
+            Entry::Substring,
+            &part.0,
+          )?;
+          added = added + 1;
+        }
+        "x" => {
+          let mut file = category.append()?;
+
+          for norm in words.keys() {
+            blacklist::write_entry(&mut file, Entry::Exception, &norm.0)?;
+            added = added + 1;
+          }
+        }
+        "o" => break review_each(category, &words, &mut added)?,
+        "n" | "" => {}
+        "q" => break Decision::Quit,
+        _ => continue,
+      }
+
+      break Decision::Next;
+    };
+
+    if let Decision::Quit = decision {
+      break;
+    }
+  }
+
+  println!("added {} rule(s)", added);
+
+  Ok(())
+}
@@ -0,0 +1,261 @@
+use super::*;
+
+// A word is made of a subset of another's letters exactly when its sorted
+// letters are a subsequence of the other's sorted letters, so storing
+// depermuted words in a trie lets a search skip every branch that uses a
+// letter the key doesn't have (or has too few of).
+pub struct SubsetIndex {
+  nodes: Vec<Node>,
+}
+
+#[derive(Default)]
+struct Node {
+  children: Vec<(char, usize)>, // Sorted by letter
+  word: Option<Depermuted>,
+}
+
+impl SubsetIndex {
+  pub fn new<'a, I>(words: I) -> Self
+  where
+    I: IntoIterator<Item = &'a Depermuted>,
+  {
+    let mut ret = Self {
+      nodes: vec![Node::default()],
+    };
+
+    for word in words {
+      let mut node = 0;
+
+      for c in word.0.chars() {
+        let found = ret.nodes[node].children.binary_search_by_key(&c, |e| e.0);
+
+        node = match found {
+          Ok(i) => ret.nodes[node].children[i].1,
+          Err(i) => {
+            let id = ret.nodes.len();
+            ret.nodes.push(Node::default());
+            ret.nodes[node].children.insert(i, (c, id));
+            id
+          }
+        };
+      }
+
+      ret.nodes[node].word = Some(word.clone());
+    }
+
+    ret
+  }
+
+  // Finds every word in the index made from some of the letters of key
+  pub fn subsets(&self, key: &Depermuted) -> Vec<&Depermuted> {
+    let key: Vec<char> = key.0.chars().collect();
+    let mut ret = Vec::new();
+
+    self.search(0, &key, &mut ret);
+
+    ret
+  }
+
+  fn search<'a>(
+    &'a self,
+    node: usize,
+    key: &[char],
+    out: &mut Vec<&'a Depermuted>,
+  ) {
+    let node = &self.nodes[node];
+
+    if let Some(ref word) = node.word {
+      out.push(word);
+    }
+
+    let mut i = 0;
+
+    for &(c, child) in &node.children {
+      while i < key.len() && key[i] < c {
+        i = i + 1;
+      }
+
+      if i == key.len() {
+        break;
+      }
+
+      if key[i] == c {
+        self.search(child, &key[i + 1..], out);
+      }
+    }
+  }
+}
+
+// Sorts a word's letters, so every anagram of it comes out the same
+pub fn depermute(word: &str) -> Depermuted {
+  let mut chars: Vec<_> = word.chars().collect();
+  chars.sort();
+  Depermuted(chars.into_iter().collect())
+}
+
+pub type CharCounts = HashMap<char, usize>;
+
+pub fn count_chars(s: &str) -> CharCounts {
+  let mut ret = CharCounts::new();
+
+  for c in s.chars() {
+    use std::collections::hash_map::Entry::*;
+
+    match ret.entry(c) {
+      Occupied(o) => {
+        let val = o.into_mut();
+        *val = *val + 1;
+      }
+      Vacant(v) => {
+        v.insert(1);
+      }
+    }
+  }
+
+  ret
+}
+
+pub fn is_subseq(count: &CharCounts, of: &CharCounts) -> bool {
+  count.iter().all(|(c, n)| n <= of.get(c).unwrap_or(&0))
+}
+
+// Times the index against checking every word's letter counts against every
+// key, which is what stage 2 used to do, and makes sure they agree
+pub fn bench(words: &[String], profile: &Profile) -> Result<()> {
+  let words: BTreeSet<Depermuted> = {
+    let mut deperms = BTreeSet::new();
+
+    let locale = profile.locale();
+
+    for word in words {
+      let depermuted = depermute(&normalize(word, &locale).0);
+
+      if depermuted.len() < profile.min_valid_len {
+        continue;
+      }
+
+      deperms.insert(depermuted);
+    }
+
+    deperms
+  };
+
+  let keys: Vec<&Depermuted> = words
+    .iter()
+    .filter(|w| {
+      w.len() >= profile.min_len && w.len() <= profile.max_len
+    })
+    .collect();
+
+  println!(
+    "{} depermuted word(s), {} key(s)",
+    words.len(),
+    keys.len()
+  );
+
+  let secs = |start: Instant| {
+    let time = start.elapsed();
+    time.as_secs() as f64 + time.subsec_nanos() as f64 / 1e9
+  };
+
+  let start = Instant::now();
+  let index = SubsetIndex::new(&words);
+  let build_time = secs(start);
+
+  let start = Instant::now();
+  let mut indexed: Vec<Vec<&Depermuted>> =
+    keys.iter().map(|k| index.subsets(k)).collect();
+  let index_time = secs(start);
+
+  let start = Instant::now();
+  let counts: HashMap<&Depermuted, CharCounts> =
+    words.iter().map(|w| (w, count_chars(&w.0))).collect();
+  let mut naive: Vec<Vec<&Depermuted>> = keys
+    .iter()
+    .map(|k| {
+      words
+        .iter()
+        .filter(|w| {
+          w.len() <= k.len() && is_subseq(&counts[w], &counts[k])
+        })
+        .collect()
+    })
+    .collect();
+  let naive_time = secs(start);
+
+  for list in indexed.iter_mut().chain(naive.iter_mut()) {
+    list.sort();
+  }
+
+  if indexed != naive {
+    return Err(ErrorKind::Bench.into());
+  }
+
+  let found = indexed.iter().fold(0, |s, l| s + l.len());
+
+  println!("{} subword(s) found", found);
+  println!("  naive:   {:.3}s", naive_time);
+  println!(
+    "  indexed: {:.3}s (plus {:.3}s to build the index)",
+    index_time, build_time
+  );
+  println!(
+    "  speedup: {:.1}x",
+    naive_time / (index_time + build_time)
+  );
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn naive<'a>(
+    words: &'a BTreeSet<Depermuted>,
+    key: &Depermuted,
+  ) -> Vec<&'a str> {
+    let key = count_chars(&key.0);
+
+    words
+      .iter()
+      .filter(|w| is_subseq(&count_chars(&w.0), &key))
+      .map(|w| w.0.as_str())
+      .collect()
+  }
+
+  #[test]
+  fn index_matches_naive_search() {
+    let words: BTreeSet<Depermuted> = [
+      "a", "ab", "aab", "bat", "tab", "abbot", "about", "stab", "beast",
+      "banana", "nab", "çay", "ağaç", "çağ", "éa", "tabbé",
+    ].iter()
+      .map(|w| depermute(w))
+      .collect();
+    let index = SubsetIndex::new(&words);
+
+    let keys = words
+      .iter()
+      .cloned()
+      .chain(
+        ["", "z", "aabbnnst", "tabbéçay", "aaağçç", "bbbb"]
+          .iter()
+          .map(|k| depermute(k)),
+      )
+      .collect::<Vec<_>>();
+
+    for key in &keys {
+      let mut found: Vec<&str> =
+        index.subsets(key).iter().map(|w| w.0.as_str()).collect();
+      found.sort();
+
+      assert_eq!(found, naive(&words, key), "key '{}'", key.0);
+    }
+
+    // Make sure the comparison isn't trivially empty
+    assert_eq!(
+      naive(&words, &depermute("aabbnnst")),
+      vec!["a", "aab", "ab", "abn", "abst", "abt"]
+    );
+  }
+}
@@ -0,0 +1,316 @@
+use super::*;
+use unicode_normalization::UnicodeNormalization;
+
+// The lists making up the wordlist, relative to 12dicts (or alt12dicts, which
+// is only searched for lists missing from 12dicts)
+static LISTS: [(&str, &str); 5] = [
+  ("American", "2of12.txt"),
+  ("American", "2of12inf.txt"),
+  ("American", "3esl.txt"),
+  ("International", "3of6all.txt"),
+  ("International", "3of6game.txt"),
+];
+
+// Neologisms, with inflections listed after each word and arrows pointing from
+// some words to the ones they're variants of
+static NEOLOGISMS: (&str, &str) = ("Special", "neol2016.txt");
+
+static REJECT: [&str; 37] = [
+  "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p",
+  "q", "r", "s", "t", "u", "v", "w", "x", "y", "z", "BEd", "BEds", "DPhil",
+  "DPhils", "KO'd", "McCoy", "OD'd", "OK'd", "pay-TV", "Pte", "Pty", "VoIP",
+];
+
+pub struct Import {
+  pub words: Vec<String>,
+  pub unrejected: Vec<String>, // Entries in REJECT that never came up
+  pub diagnostics: Vec<(String, String)>, // Sorted pairs of (kind, word)
+}
+
+fn cleanup(s: &str) -> String {
+  lazy_static! {
+    static ref SPACE_RE: Regex = Regex::new(r"\s+").unwrap();
+  }
+
+  let s: String = s.trim().nfkc().collect();
+
+  SPACE_RE.replace_all(&s, " ").into_owned()
+}
+
+fn find_list(dir: &Path, (sub, name): (&str, &str)) -> Result<PathBuf> {
+  let main = dir.join("12dicts").join(sub).join(name);
+  let alt = dir.join("alt12dicts").join(name);
+
+  if main.exists() {
+    Ok(main)
+  } else if alt.exists() {
+    Ok(alt)
+  } else {
+    Err(ErrorKind::MissingList(main.to_string_lossy().into_owned()).into())
+  }
+}
+
+fn read_lines(path: &Path) -> Result<Vec<String>> {
+  let file = BufReader::new(File::open(path)?);
+  let mut ret = Vec::new();
+
+  for line in file.lines() {
+    ret.push(cleanup(&line?));
+  }
+
+  Ok(ret)
+}
+
+// Splits a neologism entry around any '-> [word]' arrows, keeping both sides
+fn split_arrows(s: &str) -> Vec<String> {
+  lazy_static! {
+    static ref ARROW_RE: Regex = Regex::new(r"\s*->\s*\[\s*(.+)\s*\]").unwrap();
+  }
+
+  let mut ret = Vec::new();
+  let mut last = 0;
+
+  for caps in ARROW_RE.captures_iter(s) {
+    let whole = caps.get(0).unwrap();
+
+    ret.push(s[last..whole.start()].to_string());
+    ret.push(caps[1].to_string());
+    last = whole.end();
+  }
+
+  ret.push(s[last..].to_string());
+
+  ret
+}
+
+// Looks for pt or cd not following a vowel, with pt also allowed after l, m or
+// r, which tends to show up in abbreviations
+fn is_weird(s: &str) -> bool {
+  let chars: Vec<char> = s.chars().collect();
+
+  (0..chars.len().saturating_sub(1)).any(|i| {
+    let prev = if i > 0 { Some(chars[i - 1]) } else { None };
+    let after_vowel = prev.map_or(false, |c| "aeiouy".contains(c));
+
+    match (chars[i], chars[i + 1]) {
+      ('p', 't') => !after_vowel && !prev.map_or(false, |c| "lmr".contains(c)),
+      ('c', 'd') => !after_vowel,
+      _ => false,
+    }
+  })
+}
+
+// Reads the 12dicts lists from dir (which should contain 12dicts and
+// alt12dicts), dropping abbreviations, acronyms, stray letters and anything in
+// REJECT, and noting anything else that looks suspicious
+pub fn import(dir: &str) -> Result<Import> {
+  lazy_static! {
+    static ref TRAIL_RE: Regex = Regex::new(r"[%!^&$+;:>]+$").unwrap();
+    static ref SPLIT_RE: Regex = Regex::new(r"\s+").unwrap();
+    static ref ACRONYM_RE: Regex =
+      Regex::new(r"^(?:[^\p{Ll}]{2,}(?:s|ed|ing)?)$|^-|[-.]$").unwrap();
+    static ref NONWORD_RE: Regex = Regex::new(r"[^A-Za-z0-9_]+").unwrap();
+    static ref VOWEL_RE: Regex = Regex::new(r"[aeiouy]").unwrap();
+    static ref PART_RE: Regex = Regex::new(r"[\s+\p{P}]").unwrap();
+    static ref CAPS_RE: Regex = Regex::new(r"[\p{Lu}\p{Lt}]").unwrap();
+    static ref PROPER_RE: Regex =
+      Regex::new(r"\P{L}*[\p{Lu}\p{Lt}]\p{Ll}*\P{L}*").unwrap();
+  }
+
+  let dir = Path::new(dir);
+  let mut entries: Vec<String> = Vec::new();
+
+  for list in LISTS.iter() {
+    entries.extend(read_lines(&find_list(dir, *list)?)?);
+  }
+
+  for line in read_lines(&find_list(dir, NEOLOGISMS)?)? {
+    for group in line.split(',') {
+      for word in split_arrows(&cleanup(group)) {
+        let word = cleanup(&word);
+
+        if !word.is_empty() {
+          entries.push(word);
+        }
+      }
+    }
+  }
+
+  let entries: BTreeSet<String> = entries
+    .iter()
+    .map(|w| TRAIL_RE.replace(&cleanup(w), "").into_owned())
+    .collect();
+
+  let mut unrejected: BTreeSet<&str> = REJECT.iter().cloned().collect();
+  let mut diagnostics: Vec<(String, String)> = Vec::new();
+  let mut words: Vec<String> = Vec::new();
+
+  for word in entries {
+    let mut diag = |kind: &str| diagnostics.push((kind.into(), word.clone()));
+
+    let rejected = SPLIT_RE.split(&word).any(|w| {
+      if REJECT.contains(&w) {
+        unrejected.remove(w);
+        true
+      } else if ACRONYM_RE.is_match(w) {
+        diag("R_REJECT");
+        true
+      } else {
+        false
+      }
+    });
+
+    if rejected {
+      continue;
+    }
+
+    let stripped = NONWORD_RE.replace_all(&word, "").to_lowercase();
+
+    if stripped.is_empty() {
+      continue;
+    }
+
+    let no_vowels = !VOWEL_RE.is_match(&stripped);
+
+    if no_vowels && stripped.chars().count() < 3 {
+      diag("R_DROP");
+      continue;
+    }
+
+    // The rest are only worth a look, and don't stop the word being used
+    if no_vowels {
+      diag("N_NOVOWEL");
+    } else if is_weird(&stripped) {
+      diag("N_WEIRD");
+    } else if PART_RE
+      .split(&word)
+      .any(|p| CAPS_RE.find_iter(p).count() > 1)
+    {
+      diag("N_CAPS");
+    } else if PART_RE.split(&word).any(|p| PROPER_RE.is_match(p)) {
+      diag("N_PROP");
+    }
+
+    words.push(word);
+  }
+
+  diagnostics.sort();
+
+  Ok(Import {
+    words,
+    unrejected: unrejected.into_iter().map(|w| w.to_string()).collect(),
+    diagnostics,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // What scripts/process-12dicts made of the same lines
+  #[test]
+  fn imports_fixture() {
+    let import = import(&format!(
+      "{}/tests/fixtures/12dicts",
+      env!("CARGO_MANIFEST_DIR")
+    )).unwrap();
+
+    assert_eq!(
+      import.words,
+      vec![
+        "Mgr",
+        "PayPal",
+        "Xmas",
+        "a",
+        "aardvark",
+        "aardvarks",
+        "abandoned",
+        "ad hoc",
+        "app",
+        "apps",
+        "excerpt",
+        "fish",
+        "hmm",
+        "houmous",
+        "houmouses",
+        "humus",
+        "humuses",
+        "kept",
+        "many spaces",
+        "nth",
+        "psst",
+        "ptarmigan",
+        "receipt",
+        "sample",
+        "sampling",
+        "script",
+        "sculpt",
+        "tempt",
+        "vitamin C",
+      ]
+    );
+
+    for w in &["McCoy", "VoIP", "b", "pay-TV", "x"] {
+      assert!(!import.unrejected.contains(&w.to_string()));
+    }
+
+    assert_eq!(import.unrejected.len(), REJECT.len() - 5);
+
+    let diagnostics: Vec<_> = import
+      .diagnostics
+      .iter()
+      .map(|(k, w)| (k.as_str(), w.as_str()))
+      .collect();
+
+    assert_eq!(
+      diagnostics,
+      vec![
+        ("N_CAPS", "PayPal"),
+        ("N_NOVOWEL", "Mgr"),
+        ("N_NOVOWEL", "hmm"),
+        ("N_NOVOWEL", "nth"),
+        ("N_NOVOWEL", "psst"),
+        ("N_PROP", "Xmas"),
+        ("N_PROP", "vitamin C"),
+        ("N_WEIRD", "ptarmigan"),
+        ("R_DROP", "Dr"),
+        ("R_REJECT", "-ism"),
+        ("R_REJECT", "Mr."),
+        ("R_REJECT", "TV"),
+        ("R_REJECT", "TV dinner"),
+        ("R_REJECT", "etc."),
+      ]
+    );
+  }
+
+  #[test]
+  fn missing_list() {
+    assert!(import(&format!("{}/tests", env!("CARGO_MANIFEST_DIR"))).is_err());
+  }
+
+  #[test]
+  fn split_arrows() {
+    assert_eq!(
+      super::split_arrows("loonies -> [loony]"),
+      vec!["loonies", "loony", ""]
+    );
+    // As greedy as the script's regex
+    assert_eq!(
+      super::split_arrows("a ->[ b ] c -> [d]"),
+      vec!["a", "b ] c -> [d", ""]
+    );
+    assert_eq!(super::split_arrows("blogs"), vec!["blogs"]);
+  }
+
+  // The script's /(?<![aeiouy])(?:(?<![lmr])pt|cd)/
+  #[test]
+  fn is_weird() {
+    for s in &["pt", "cd", "ptarmigan", "spt", "lcd", "mcd", "rcd", "xpt"] {
+      assert!(super::is_weird(s), "{}", s);
+    }
+
+    for s in &["", "p", "apt", "kept", "lpt", "mpt", "rpt", "acd", "dc"] {
+      assert!(!super::is_weird(s), "{}", s);
+    }
+  }
+}
@@ -0,0 +1,497 @@
+use super::*;
+use diesel::{
+  expression::functions::aggregate_ordering::max, prelude::*,
+  sqlite::SqliteConnection,
+};
+use models::*;
+
+// SQLite only allows so many bound parameters in one statement
+static CHUNK_SIZE: usize = 500;
+
+// Works out which words changed, from explicit lists of added and removed
+// words, the difference between the previous and current word lists, or both
+pub fn changed_words(
+  words: &[String],
+  added: Option<&str>,
+  removed: Option<&str>,
+  previous: Option<&str>,
+  locale: &Locale,
+) -> Result<BTreeSet<Normalized>> {
+  let mut changed = BTreeSet::new();
+
+  for list in added.iter().chain(removed.iter()) {
+    let list = input::read_plain(list)?;
+
+    changed.extend(list.iter().map(|w| normalize(w, locale)));
+  }
+
+  if let Some(previous) = previous {
+    let old: BTreeSet<String> =
+      input::read_plain(previous)?.into_iter().collect();
+    let new: BTreeSet<String> = words.iter().cloned().collect();
+
+    changed.extend(
+      old.symmetric_difference(&new).map(|w| normalize(w, locale)),
+    );
+  }
+
+  changed.retain(|n| !n.0.is_empty());
+
+  Ok(changed)
+}
+
+fn next_id(found: Option<i32>) -> i32 {
+  found.map_or(0, |i| i + 1)
+}
+
+// Rebuilds only the sets containing a changed word, along with the forms and
+// definitions of any word that was in one of them, all in one transaction
+pub fn update(
+  conn: &SqliteConnection,
+  s1: &Stage1,
+  changed: &BTreeSet<Normalized>,
+  defs: Option<&HashMap<Normalized, Vec<String>>>,
+  profile: &Profile,
+  constraints: &Constraints,
+) -> Result<()> {
+  use schema::{
+    bonuses::dsl as b, definitions::dsl as d, form_ids::dsl as fi,
+    forms::dsl as f, set_ids::dsl as si, set_keys::dsl as sk, sets::dsl as s,
+    transitions::dsl as t,
+  };
+
+  let changed_deperms: BTreeSet<Depermuted> =
+    changed.iter().map(|n| subset::depermute(&n.0)).collect();
+  let changed_index = SubsetIndex::new(&changed_deperms);
+
+  let affects =
+    |key: &Depermuted| !changed_index.subsets(key).is_empty();
+
+  let start = Instant::now();
+
+  conn.transaction::<_, Error, _>(|| {
+    println!("finding affected sets...");
+
+    let old_keys: Vec<SetIdQ> = si::set_ids.load(conn)?;
+    let old_keys: Vec<SetIdQ> = old_keys
+      .into_iter()
+      .filter(|k| affects(&Depermuted(k.key.clone())))
+      .collect();
+
+    let mut new_keys: Vec<&Depermuted> = Vec::new();
+
+    for len in profile.min_len..profile.max_len + 1 {
+      if let Some(group) = s1.len_groups.get(&len) {
+        new_keys.extend(group.iter().filter(|k| affects(k)));
+      }
+    }
+
+    new_keys.sort();
+
+    println!(
+      "{} old set(s) affected, {} to rebuild",
+      old_keys.len(),
+      new_keys.len()
+    );
+
+    // Every word that was in an affected set might now be orphaned
+    let mut candidates: BTreeSet<Normalized> = changed.clone();
+
+    for chunk in old_keys.chunks(CHUNK_SIZE) {
+      let ids: Vec<i32> = chunk.iter().map(|k| k.id).collect();
+      let keys: Vec<&str> = chunk.iter().map(|k| k.key.as_str()).collect();
+
+      let norms: Vec<String> =
+        s::sets.select(s::norm).filter(s::id.eq_any(&ids)).load(conn)?;
+      let bonus_norms: Vec<String> =
+        b::bonuses.select(b::norm).filter(b::id.eq_any(&ids)).load(conn)?;
+
+      candidates.extend(norms.into_iter().chain(bonus_norms).map(Normalized));
+
+      diesel::delete(s::sets.filter(s::id.eq_any(&ids))).execute(conn)?;
+      diesel::delete(b::bonuses.filter(b::id.eq_any(&ids))).execute(conn)?;
+      diesel::delete(si::set_ids.filter(si::key.eq_any(&keys)))
+        .execute(conn)?;
+      diesel::delete(sk::set_keys.filter(sk::key.eq_any(&keys)))
+        .execute(conn)?;
+    }
+
+    println!("rebuilding sets...");
+
+    let mut set_id = next_id(si::set_ids.select(max(si::id)).first(conn)?);
+    let mut set_oid = next_id(s::sets.select(max(s::oid)).first(conn)?);
+    let mut bonus_oid = next_id(b::bonuses.select(max(b::oid)).first(conn)?);
+    let mut key_oid = next_id(sk::set_keys.select(max(sk::oid)).first(conn)?);
+
+    let mut rejected: BTreeMap<Reject, usize> = BTreeMap::new();
+
+    for key in &new_keys {
+      let (list, bonus) = build_set(s1, key, profile.max_len_difference);
+
+      if let Some(reason) = constraints.check(key, &list) {
+        let count = rejected.entry(reason).or_insert(0);
+        *count = *count + 1;
+        continue;
+      }
+
+      diesel::insert_into(si::set_ids)
+        .values(&SetId {
+          key: &key.0,
+          id: set_id,
+        })
+        .execute(conn)?;
+
+      diesel::insert_into(sk::set_keys)
+        .values(&SetKey {
+          oid: key_oid,
+          len: key.len() as i32,
+          key: &key.0,
+        })
+        .execute(conn)?;
+
+      key_oid = key_oid + 1;
+
+      let insert_sets: Vec<Set> = list
+        .iter()
+        .enumerate()
+        .map(|(i, n)| Set {
+          oid: set_oid + i as i32,
+          id: set_id,
+          norm: &n.0,
+        })
+        .collect();
+
+      let insert_bonuses: Vec<Bonus> = bonus
+        .iter()
+        .enumerate()
+        .map(|(i, n)| Bonus {
+          oid: bonus_oid + i as i32,
+          id: set_id,
+          norm: &n.0,
+        })
+        .collect();
+
+      diesel::insert_into(s::sets)
+        .values(&insert_sets)
+        .execute(conn)?;
+      diesel::insert_into(b::bonuses)
+        .values(&insert_bonuses)
+        .execute(conn)?;
+
+      set_oid = set_oid + insert_sets.len() as i32;
+      bonus_oid = bonus_oid + insert_bonuses.len() as i32;
+      set_id = set_id + 1;
+
+      candidates.extend(list.into_iter().chain(bonus));
+    }
+
+    for (reason, count) in &rejected {
+      println!("  dropped for {}: {}", reason, count);
+    }
+
+    println!("updating forms for {} word(s)...", candidates.len());
+
+    let used: HashSet<String> = {
+      let norms: Vec<String> =
+        s::sets.select(s::norm).distinct().load(conn)?;
+      let bonus_norms: Vec<String> =
+        b::bonuses.select(b::norm).distinct().load(conn)?;
+
+      norms.into_iter().chain(bonus_norms).collect()
+    };
+
+    let candidates: Vec<&Normalized> = candidates.iter().collect();
+
+    for chunk in candidates.chunks(CHUNK_SIZE) {
+      let norms: Vec<&str> = chunk.iter().map(|n| n.0.as_str()).collect();
+
+      let ids: Vec<i32> = fi::form_ids
+        .select(fi::id)
+        .filter(fi::norm.eq_any(&norms))
+        .load(conn)?;
+
+      diesel::delete(f::forms.filter(f::id.eq_any(&ids))).execute(conn)?;
+      diesel::delete(fi::form_ids.filter(fi::norm.eq_any(&norms)))
+        .execute(conn)?;
+
+      // Definitions are only replaced if new ones were given, but are always
+      // dropped along with their word
+      let stale: Vec<&str> = norms
+        .iter()
+        .cloned()
+        .filter(|n| defs.is_some() || !used.contains(*n))
+        .collect();
+
+      diesel::delete(d::definitions.filter(d::norm.eq_any(&stale)))
+        .execute(conn)?;
+    }
+
+    let mut form_id = next_id(fi::form_ids.select(max(fi::id)).first(conn)?);
+    let mut form_oid = next_id(f::forms.select(max(f::oid)).first(conn)?);
+    let mut def_oid = next_id(d::definitions.select(max(d::oid)).first(conn)?);
+
+    for norm in candidates {
+      let forms = match s1.forms.get(norm) {
+        Some(f) if used.contains(&norm.0) => f,
+        _ => continue,
+      };
+
+      diesel::insert_into(fi::form_ids)
+        .values(&FormId {
+          norm: &norm.0,
+          id: form_id,
+        })
+        .execute(conn)?;
+
+      let insert_forms: Vec<Form> = forms
+        .iter()
+        .enumerate()
+        .map(|(i, form)| Form {
+          oid: form_oid + i as i32,
+          id: form_id,
+          blank: &form.blanked,
+          full: &form.full,
+        })
+        .collect();
+
+      diesel::insert_into(f::forms)
+        .values(&insert_forms)
+        .execute(conn)?;
+
+      form_oid = form_oid + insert_forms.len() as i32;
+      form_id = form_id + 1;
+
+      if let Some(list) = defs.and_then(|d| d.get(norm)) {
+        let insert_defs: Vec<Definition> = list
+          .iter()
+          .enumerate()
+          .map(|(i, def)| Definition {
+            oid: def_oid + i as i32,
+            norm: &norm.0,
+            definition: def,
+          })
+          .collect();
+
+        diesel::insert_into(d::definitions)
+          .values(&insert_defs)
+          .execute(conn)?;
+
+        def_oid = def_oid + insert_defs.len() as i32;
+      }
+    }
+
+    // The letter model covers the whole list, but it's small enough to redo
+    println!("rebuilding letter model...");
+
+    let model = build_model(s1.forms.keys());
+
+    let insert_transitions: Vec<Transition> = model
+      .iter()
+      .enumerate()
+      .map(|(i, ((context, next), weight))| Transition {
+        oid: i as i32,
+        context,
+        next,
+        weight: *weight,
+      })
+      .collect();
+
+    diesel::delete(t::transitions).execute(conn)?;
+    diesel::insert_into(t::transitions)
+      .values(&insert_transitions)
+      .execute(conn)?;
+
+    Ok(())
+  })?;
+
+  let time = start.elapsed();
+
+  println!(
+    "updated in {}.{:02}s",
+    time.as_secs(),
+    time.subsec_millis() / 10
+  );
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  static OLD: [&str; 18] = [
+    "abet", "at", "ate", "bast", "bat", "be", "beast", "beat", "best", "bet",
+    "east", "eat", "sat", "seat", "set", "stab", "tab", "tea",
+  ];
+
+  // Drops stab, adds bates and tabs
+  static NEW: [&str; 19] = [
+    "abet", "at", "ate", "bast", "bat", "bates", "be", "beast", "beat", "best",
+    "bet", "east", "eat", "sat", "seat", "set", "tab", "tabs", "tea",
+  ];
+
+  // Everything in a database besides the ids tying it together
+  #[derive(Debug, PartialEq)]
+  struct Contents {
+    sets: BTreeMap<String, (Vec<String>, Vec<String>)>,
+    set_keys: BTreeSet<(i32, String)>,
+    forms: BTreeMap<String, Vec<(String, String)>>,
+    definitions: BTreeMap<String, Vec<String>>,
+    transitions: Vec<(String, String, f64)>,
+  }
+
+  fn profile() -> Profile {
+    Profile {
+      min_len: 4,
+      max_len: 5,
+      max_len_difference: 2,
+      blacklist: format!(
+        "{}/tests/fixtures/update/blacklist",
+        env!("CARGO_MANIFEST_DIR")
+      ),
+      workers: 2,
+      ..Profile::default()
+    }
+  }
+
+  fn defs() -> HashMap<Normalized, Vec<String>> {
+    ["beast", "stab", "tabs", "tea"]
+      .iter()
+      .map(|w| (Normalized(w.to_string()), vec![format!("a {}", w)]))
+      .collect()
+  }
+
+  fn stage_1(words: &[&str]) -> Arc<Stage1> {
+    Arc::new(
+      super::stage_1(
+        words.iter().map(|w| w.to_string()).collect(),
+        None,
+        &profile(),
+        &mut Report::default(),
+      ).unwrap(),
+    )
+  }
+
+  fn build(words: &[&str]) -> SqliteConnection {
+    let profile = profile();
+    let s1 = stage_1(words);
+    let constraints = Constraints::new(&profile).unwrap();
+    let s2 =
+      stage_2(&s1, &profile, &constraints, &mut Report::default()).unwrap();
+    let model = build_model(s1.forms.keys());
+
+    let mut forms = s1.forms.clone();
+    forms.retain(|k, _| s2.used_words.contains(k));
+
+    let mut defs = defs();
+    defs.retain(|k, _| forms.contains_key(k));
+
+    let conn = SqliteConnection::establish(":memory:").unwrap();
+
+    write_database(&conn, &forms, &s2, &defs, &model, &mut Report::default())
+      .unwrap();
+
+    conn
+  }
+
+  fn contents(conn: &SqliteConnection) -> Contents {
+    use schema::{
+      bonuses::dsl as b, definitions::dsl as d, form_ids::dsl as fi,
+      forms::dsl as f, set_ids::dsl as si, set_keys::dsl as sk,
+      sets::dsl as s, transitions::dsl as t,
+    };
+
+    let set_ids: Vec<SetIdQ> = si::set_ids.load(conn).unwrap();
+    let form_ids: Vec<FormIdQ> = fi::form_ids.load(conn).unwrap();
+
+    let sets = set_ids
+      .iter()
+      .map(|k| {
+        let norms = s::sets
+          .select(s::norm)
+          .filter(s::id.eq(k.id))
+          .order(s::oid)
+          .load(conn)
+          .unwrap();
+        let bonus_norms = b::bonuses
+          .select(b::norm)
+          .filter(b::id.eq(k.id))
+          .order(b::oid)
+          .load(conn)
+          .unwrap();
+
+        (k.key.clone(), (norms, bonus_norms))
+      })
+      .collect();
+
+    let forms = form_ids
+      .iter()
+      .map(|n| {
+        let forms = f::forms
+          .select((f::blank, f::full))
+          .filter(f::id.eq(n.id))
+          .order(f::oid)
+          .load(conn)
+          .unwrap();
+
+        (n.norm.clone(), forms)
+      })
+      .collect();
+
+    let mut definitions = BTreeMap::new();
+
+    for def in d::definitions.order(d::oid).load::<DefinitionQ>(conn).unwrap() {
+      definitions
+        .entry(def.norm)
+        .or_insert_with(Vec::new)
+        .push(def.definition);
+    }
+
+    Contents {
+      sets,
+      set_keys: sk::set_keys
+        .select((sk::len, sk::key))
+        .load::<(i32, String)>(conn)
+        .unwrap()
+        .into_iter()
+        .collect(),
+      forms,
+      definitions,
+      transitions: t::transitions
+        .select((t::context, t::next, t::weight))
+        .order((t::context, t::next))
+        .load(conn)
+        .unwrap(),
+    }
+  }
+
+  #[test]
+  fn update_matches_fresh_build() {
+    let locale = profile().locale();
+
+    let conn = build(&OLD);
+    let before = contents(&conn);
+
+    let changed: BTreeSet<Normalized> = OLD
+      .iter()
+      .filter(|w| !NEW.contains(w))
+      .chain(NEW.iter().filter(|w| !OLD.contains(w)))
+      .map(|w| normalize(w, &locale))
+      .collect();
+
+    update(
+      &conn,
+      &stage_1(&NEW),
+      &changed,
+      Some(&defs()),
+      &profile(),
+      &Constraints::new(&profile()).unwrap(),
+    ).unwrap();
+
+    let after = contents(&conn);
+
+    assert!(after != before);
+    assert_eq!(after, contents(&build(&NEW)));
+    assert_eq!(verify::verify(&conn).unwrap(), 0);
+  }
+}
@@ -0,0 +1,153 @@
+use diesel::{prelude::*, sqlite::SqliteConnection};
+use models::*;
+use schema;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use subset::{count_chars, depermute, is_subseq};
+use Result;
+
+// Checks the database for anything that would confuse the game, printing each
+// problem found and returning how many there were
+pub fn verify(conn: &SqliteConnection) -> Result<usize> {
+  println!("loading tables...");
+
+  let form_ids: Vec<FormIdQ> = schema::form_ids::table.load(conn)?;
+  let forms: Vec<FormQ> = schema::forms::table.load(conn)?;
+  let set_ids: Vec<SetIdQ> = schema::set_ids::table.load(conn)?;
+  let sets: Vec<SetQ> = schema::sets::table.load(conn)?;
+  let bonuses: Vec<BonusQ> = schema::bonuses::table.load(conn)?;
+  // The game has no use for a set_keys model, so these are just (len, key)
+  let set_keys: Vec<(i32, String)> = {
+    use schema::set_keys::dsl::*;
+
+    set_keys.select((len, key)).load(conn)?
+  };
+
+  let mut problems: Vec<String> = Vec::new();
+
+  let form_ids_by_norm: HashMap<&str, i32> =
+    form_ids.iter().map(|f| (f.norm.as_str(), f.id)).collect();
+  let form_ids_with_forms: HashSet<i32> = forms.iter().map(|f| f.id).collect();
+
+  let set_ids_by_key: HashMap<&str, i32> =
+    set_ids.iter().map(|s| (s.key.as_str(), s.id)).collect();
+
+  let mut set_words: HashMap<i32, HashSet<&str>> = HashMap::new();
+  let mut bonus_words: HashMap<i32, HashSet<&str>> = HashMap::new();
+
+  for set in &sets {
+    use std::collections::hash_map::Entry::*;
+
+    match set_words.entry(set.id) {
+      Vacant(v) => v.insert(HashSet::new()),
+      Occupied(o) => o.into_mut(),
+    }.insert(&set.norm);
+  }
+
+  for bonus in &bonuses {
+    use std::collections::hash_map::Entry::*;
+
+    match bonus_words.entry(bonus.id) {
+      Vacant(v) => v.insert(HashSet::new()),
+      Occupied(o) => o.into_mut(),
+    }.insert(&bonus.norm);
+  }
+
+  let mut anagrams: HashMap<String, Vec<&str>> = HashMap::new();
+
+  for form_id in &form_ids {
+    use std::collections::hash_map::Entry::*;
+
+    match anagrams.entry(depermute(&form_id.norm).0) {
+      Vacant(v) => v.insert(Vec::new()),
+      Occupied(o) => o.into_mut(),
+    }.push(&form_id.norm);
+  }
+
+  println!("checking forms...");
+
+  for form_id in &form_ids {
+    if !form_ids_with_forms.contains(&form_id.id) {
+      problems.push(format!(
+        "word '{}' (form id {}) has no forms",
+        form_id.norm, form_id.id
+      ));
+    }
+  }
+
+  let norms: BTreeSet<&str> = sets
+    .iter()
+    .map(|s| s.norm.as_str())
+    .chain(bonuses.iter().map(|b| b.norm.as_str()))
+    .collect();
+
+  for norm in norms {
+    if !form_ids_by_norm.contains_key(norm) {
+      problems.push(format!("word '{}' has no form_ids row", norm));
+    }
+  }
+
+  println!("checking keys...");
+
+  for (len, key) in &set_keys {
+    if !set_ids_by_key.contains_key(key.as_str()) {
+      problems.push(format!("key '{}' has no set_ids row", key));
+    }
+
+    if key.chars().count() != *len as usize {
+      problems.push(format!("key '{}' is listed under length {}", key, len));
+    }
+
+    if depermute(key).0 != *key {
+      problems.push(format!("key '{}' is not sorted", key));
+    }
+  }
+
+  println!("checking sets...");
+
+  for set_id in &set_ids {
+    let words = match set_words.get(&set_id.id) {
+      Some(w) => w,
+      None => {
+        problems.push(format!(
+          "set '{}' (id {}) has no words",
+          set_id.key, set_id.id
+        ));
+        continue;
+      }
+    };
+
+    let len = set_id.key.chars().count();
+    let count = count_chars(&set_id.key);
+
+    for word in words {
+      if !is_subseq(&count_chars(word), &count) {
+        problems.push(format!(
+          "set '{}' contains '{}', which isn't made from its letters",
+          set_id.key, word
+        ));
+      }
+    }
+
+    if !words.iter().any(|w| w.chars().count() == len) {
+      problems.push(format!("set '{}' has no full-length words", set_id.key));
+    }
+
+    // Bonus-only words can be anagrams of the key without being in the set
+    let bonus = bonus_words.get(&set_id.id);
+
+    for word in anagrams.get(&set_id.key).into_iter().flat_map(|a| a) {
+      if !words.contains(word) && !bonus.map_or(false, |b| b.contains(word)) {
+        problems.push(format!(
+          "set '{}' is missing its own word '{}'",
+          set_id.key, word
+        ));
+      }
+    }
+  }
+
+  for problem in &problems {
+    println!("  {}", problem);
+  }
+
+  Ok(problems.len())
+}
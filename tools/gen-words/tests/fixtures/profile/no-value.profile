@@ -0,0 +1,2 @@
+min-len = 4
+max-len
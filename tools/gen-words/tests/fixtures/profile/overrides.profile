@@ -0,0 +1,5 @@
+# Comments and blank lines are skipped
+
+  max-len = 7
+blacklist-categories = slurs, , drugs
+whitelist =
@@ -0,0 +1,205 @@
+extern crate bincode;
+extern crate rand;
+extern crate serde;
+
+#[macro_use]
+extern crate error_chain;
+#[macro_use]
+extern crate serde_derive;
+
+// Shared with the game, which only needs part of it
+#[allow(dead_code)]
+mod markov;
+
+use markov::{FreqTable, Markov};
+use std::{
+  cmp::Ordering,
+  collections::{BTreeMap, VecDeque},
+  env,
+  fs::File,
+  io::{self, prelude::*, BufReader, BufWriter},
+};
+
+error_chain! {
+  foreign_links {
+    Bincode(bincode::Error);
+    Io(io::Error);
+  }
+
+  errors {
+    InvalidArg(expect: String) {
+      description("invalid arguments"),
+      display("invalid arguments: expected {}", expect),
+    }
+
+    ArgParse(msg: String) {
+      description("argument parsing failed"),
+      display("argument parsing failed: {}", msg),
+    }
+  }
+}
+
+// Splits a corpus into lowercase runs of letters, so it can be plain prose or
+// a wordlist with one word per line
+fn read_corpus(file: &str) -> Result<Vec<String>> {
+  let file = BufReader::new(File::open(file)?);
+  let mut words = Vec::new();
+
+  for line in file.lines() {
+    let line = line?.to_lowercase();
+
+    for word in line.split(|c: char| !c.is_alphabetic()) {
+      if !word.is_empty() {
+        words.push(word.to_string());
+      }
+    }
+  }
+
+  Ok(words)
+}
+
+fn train(corpus: &str, out: &str, order: usize) -> Result<()> {
+  let words = read_corpus(corpus)?;
+
+  println!("read {} word(s)", words.len());
+
+  let table =
+    markov::analyze_corpus(words.iter().map(|w| (1.0, w.chars())), order);
+
+  println!("{} context(s)", table.len());
+
+  markov::save(&table, BufWriter::new(File::create(out)?))?;
+
+  println!("saved to {}", out);
+
+  Ok(())
+}
+
+fn print_probabilities(markov: &Markov<char>, context: &str) {
+  let history: Vec<char> = context.chars().collect();
+  let (len, mut probs) = markov.probabilities(&history);
+
+  probs.sort_by(|(a, p), (b, q)| {
+    q.partial_cmp(p).unwrap_or(Ordering::Equal).then(a.cmp(b))
+  });
+
+  let used: String = history[history.len() - len..].iter().collect();
+
+  if len < history.len() {
+    println!("{} (backed off to '{}'):", context, used);
+  } else {
+    println!("{}:", context);
+  }
+
+  if probs.is_empty() {
+    println!("  (never seen)");
+  }
+
+  for (to, p) in probs {
+    println!("  {} {:.4}", to, p);
+  }
+}
+
+fn inspect(file: &str, contexts: Vec<String>) -> Result<()> {
+  let table: FreqTable<char> = markov::load(BufReader::new(File::open(file)?))?;
+
+  let mut lens: BTreeMap<usize, usize> = BTreeMap::new();
+
+  for context in table.keys() {
+    let count = lens.entry(context.len()).or_insert(0);
+    *count = *count + 1;
+  }
+
+  let mut letters: Vec<char> = table
+    .keys()
+    .filter(|k| k.len() == 1)
+    .map(|k| k[0])
+    .collect();
+  letters.sort();
+
+  let markov = Markov::new(table);
+
+  println!("order {}", markov.order());
+
+  for (len, count) in lens {
+    println!("  {} context(s) of length {}", count, len);
+  }
+
+  if contexts.is_empty() {
+    for letter in letters {
+      print_probabilities(&markov, &letter.to_string());
+    }
+  } else {
+    for context in contexts {
+      print_probabilities(&markov, &context.to_lowercase());
+    }
+  }
+
+  Ok(())
+}
+
+fn run() -> Result<()> {
+  let mut args: VecDeque<_> = env::args().collect();
+  args.pop_front(); // drop argv[0]
+
+  fn parse_arg<T>(args: &mut VecDeque<String>, expect: &str) -> Result<T>
+  where
+    T: std::str::FromStr,
+    <T as std::str::FromStr>::Err: std::string::ToString,
+  {
+    match args.pop_front() {
+      Some(a) => a,
+      None => return Err(ErrorKind::InvalidArg(expect.into()).into()),
+    }.parse()
+      .map_err(|e: <T as std::str::FromStr>::Err| {
+        ErrorKind::ArgParse(e.to_string()).into()
+      })
+  }
+
+  let command: String = parse_arg(&mut args, "train or inspect")?;
+
+  match command.as_str() {
+    "train" => {
+      let corpus: String = parse_arg(&mut args, "a corpus filename")?;
+      let out: String = parse_arg(&mut args, "an output filename")?;
+
+      let mut order = 3;
+
+      while let Some(arg) = args.pop_front() {
+        match arg.as_str() {
+          "--order" => order = parse_arg(&mut args, "a context length")?,
+          a => {
+            return Err(
+              ErrorKind::ArgParse(format!("unexpected argument '{}'", a))
+                .into(),
+            )
+          }
+        }
+      }
+
+      if order == 0 {
+        return Err(
+          ErrorKind::ArgParse("order must be positive".into()).into(),
+        );
+      }
+
+      train(&corpus, &out, order)
+    }
+    "inspect" => {
+      let file: String = parse_arg(&mut args, "a model filename")?;
+
+      inspect(&file, args.into_iter().collect())
+    }
+    c => Err(ErrorKind::ArgParse(format!("unknown command '{}'", c)).into()),
+  }
+}
+
+fn main() {
+  match run() {
+    Ok(_) => return,
+    Err(e) => {
+      writeln!(io::stderr(), "an error occurred: {}", e).unwrap();
+      std::process::exit(1);
+    }
+  }
+}
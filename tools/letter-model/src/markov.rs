@@ -0,0 +1 @@
+../../../src/markov.rs
\ No newline at end of file